@@ -1,9 +1,11 @@
 //! Module for parsing and resolving recipe arguments.
+//!
+//! Arguments are defined in [`Recipe::arguments`](crate::Recipe::arguments) and referenced by placeholders in [`Recipe::command`](crate::Recipe::command).
 
-use anyhow::{Result, bail};
+use crate::Error;
 use owo_colors::OwoColorize;
-use serde::{Deserialize, de::Error};
-use std::{collections::VecDeque, fmt::Display};
+use serde::Deserialize;
+use std::{collections::VecDeque, fmt::Display, str::FromStr};
 
 /// A recipe argument defined the configuration file.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,36 +23,79 @@ impl<'de> Deserialize<'de> for ArgumentDefinition {
     {
         // Take a string and parse it into an Argument
         let arg = String::deserialize(deserializer)?;
-        Self::from_string::<D>(arg)
+        arg.parse().map_err(serde::de::Error::custom)
     }
 }
 
-impl ArgumentDefinition {
-    /// Creates a new argument definition from a string.
-    pub fn from_string<'de, D>(mut arg: String) -> std::result::Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let first = arg
-            .chars()
-            .next()
-            .ok_or_else(|| Error::custom("Empty argument"))?;
+impl FromStr for ArgumentDefinition {
+    type Err = Error;
+
+    /// Parses an argument definition from its representation in the config file, e.g. `?arg`.
+    fn from_str(arg: &str) -> Result<Self, Error> {
+        let first = arg.chars().next().ok_or(Error::EmptyArgument)?;
         let arg_type = match first {
             '?' => ArgumentType::Optional,
             '*' => ArgumentType::Variadic,
             '+' => ArgumentType::RequiredVariadic,
             _ => ArgumentType::Required,
         };
-        if arg_type != ArgumentType::Required {
-            arg.remove(0); // Remove the leading symbol
-        }
+        let name = if arg_type == ArgumentType::Required {
+            arg
+        } else {
+            &arg[1..] // Remove the leading symbol
+        };
 
-        Ok(Self {
-            name: arg,
+        Ok(Self::new(name, arg_type))
+    }
+}
+
+impl ArgumentDefinition {
+    /// Creates a new argument definition.
+    #[must_use]
+    pub fn new(name: impl Into<String>, arg_type: ArgumentType) -> Self {
+        Self {
+            name: name.into(),
             arg_type,
-        })
+        }
     }
+
+    /// Resolves the argument value, consuming values from the front of `args`.
+    ///
+    /// ## Errors
+    ///
+    /// If a required argument is not provided.
+    pub fn resolve(&self, args: &mut VecDeque<String>) -> Result<ResolvedArgument, Error> {
+        let missing = || Error::MissingArgument {
+            name: self.name.clone(),
+            arg_type: self.arg_type.clone(),
+        };
+        match self.arg_type {
+            ArgumentType::Required => {
+                let value = args.pop_front().ok_or_else(missing)?;
+                Ok(ResolvedArgument::Required(value))
+            }
+            ArgumentType::Optional => {
+                let value = args.pop_front();
+                Ok(ResolvedArgument::Optional(value))
+            }
+            ArgumentType::Variadic => {
+                // Take all remaining arguments
+                let values: Vec<String> = args.drain(..).collect();
+                Ok(ResolvedArgument::Variadic(values))
+            }
+            ArgumentType::RequiredVariadic => {
+                if args.is_empty() {
+                    return Err(missing());
+                }
+                // Take all remaining arguments
+                let values: Vec<String> = args.drain(..).collect();
+                Ok(ResolvedArgument::RequiredVariadic(values))
+            }
+        }
+    }
+
     /// Summarizes the argument, returning a string representation and the length.
+    #[must_use]
     pub fn summarize(&self, color: bool) -> (String, usize) {
         let symbol = match self.arg_type {
             ArgumentType::Required => "",
@@ -81,37 +126,6 @@ pub enum ArgumentType {
     RequiredVariadic,
 }
 
-impl ArgumentType {
-    /// Resolves the argument value.
-    pub fn resolve(&self, args: &mut VecDeque<String>) -> Result<ResolvedArgument> {
-        match self {
-            Self::Required => {
-                let Some(value) = args.pop_front() else {
-                    bail!("Required argument not provided");
-                };
-                Ok(ResolvedArgument::Required(value))
-            }
-            Self::Optional => {
-                let value = args.pop_front();
-                Ok(ResolvedArgument::Optional(value))
-            }
-            Self::Variadic => {
-                // Take all remaining arguments
-                let values: Vec<String> = args.drain(..).collect();
-                Ok(ResolvedArgument::Variadic(values))
-            }
-            Self::RequiredVariadic => {
-                if args.is_empty() {
-                    bail!("Required variadic argument must contain at least one value");
-                }
-                // Take all remaining arguments
-                let values: Vec<String> = args.drain(..).collect();
-                Ok(ResolvedArgument::RequiredVariadic(values))
-            }
-        }
-    }
-}
-
 impl Display for ArgumentType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -138,6 +152,7 @@ pub enum ResolvedArgument {
 
 impl ResolvedArgument {
     /// Gets the argument type.
+    #[must_use]
     pub const fn arg_type(&self) -> ArgumentType {
         match self {
             Self::Required(_) => ArgumentType::Required,
@@ -148,15 +163,34 @@ impl ResolvedArgument {
     }
 
     /// Checks that the argument matches the expected type.
+    #[must_use]
     pub fn matches(&self, arg_type: &ArgumentType) -> bool {
         self.arg_type() == *arg_type
     }
+
+    /// Gets the values of the argument, empty if an optional argument is not provided.
+    #[must_use]
+    pub fn values(&self) -> &[String] {
+        match self {
+            Self::Required(value) => std::slice::from_ref(value),
+            Self::Optional(value) => value.as_slice(),
+            Self::Variadic(values) | Self::RequiredVariadic(values) => values,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Resolves an argument named `arg` of the given type.
+    fn resolve(
+        arg_type: ArgumentType,
+        args: &mut VecDeque<String>,
+    ) -> Result<ResolvedArgument, Error> {
+        ArgumentDefinition::new("arg", arg_type).resolve(args)
+    }
+
     #[test]
     fn test_argument_resolving_1() {
         // Test the resolving of required and optional arguments
@@ -166,26 +200,26 @@ mod tests {
             "arg3".to_string(),
         ]);
 
-        let arg = ArgumentType::Optional.resolve(&mut args).unwrap();
+        let arg = resolve(ArgumentType::Optional, &mut args).unwrap();
         assert_eq!(arg, ResolvedArgument::Optional(Some("arg1".to_string())));
         assert_eq!(
             args,
             VecDeque::from(vec!["arg2".to_string(), "arg3".to_string()])
         );
 
-        let arg = ArgumentType::Required.resolve(&mut args).unwrap();
+        let arg = resolve(ArgumentType::Required, &mut args).unwrap();
         assert_eq!(arg, ResolvedArgument::Required("arg2".to_string()));
         assert_eq!(args, VecDeque::from(vec!["arg3".to_string()]));
 
-        let arg = ArgumentType::Optional.resolve(&mut args).unwrap();
+        let arg = resolve(ArgumentType::Optional, &mut args).unwrap();
         assert_eq!(arg, ResolvedArgument::Optional(Some("arg3".to_string())));
         assert_eq!(args, VecDeque::from(vec![]));
 
-        let err = ArgumentType::Required.resolve(&mut args).unwrap_err();
+        let err = resolve(ArgumentType::Required, &mut args).unwrap_err();
         assert_eq!(err.to_string(), "Required argument not provided");
         assert_eq!(args, VecDeque::from(vec![]));
 
-        let arg = ArgumentType::Optional.resolve(&mut args).unwrap();
+        let arg = resolve(ArgumentType::Optional, &mut args).unwrap();
         assert_eq!(arg, ResolvedArgument::Optional(None));
         assert_eq!(args, VecDeque::from(vec![]));
     }
@@ -199,7 +233,7 @@ mod tests {
             "arg3".to_string(),
         ]);
 
-        let arg = ArgumentType::Variadic.resolve(&mut args).unwrap();
+        let arg = resolve(ArgumentType::Variadic, &mut args).unwrap();
         assert_eq!(
             arg,
             ResolvedArgument::Variadic(vec![
@@ -210,9 +244,7 @@ mod tests {
         );
         assert_eq!(args, VecDeque::from(vec![]));
 
-        let err = ArgumentType::RequiredVariadic
-            .resolve(&mut args)
-            .unwrap_err();
+        let err = resolve(ArgumentType::RequiredVariadic, &mut args).unwrap_err();
         assert_eq!(
             err.to_string(),
             "Required variadic argument must contain at least one value"
@@ -1,7 +1,8 @@
 //! Module for parsing command line arguments.
 
-use anyhow::{Result, bail};
-use std::collections::VecDeque;
+use crate::Invocation;
+use anyhow::{Context, Result, bail};
+use std::{collections::VecDeque, num::NonZeroUsize};
 
 /// Possible types of actions.
 #[derive(Debug)]
@@ -12,31 +13,176 @@ pub enum Action {
     Version,
     /// List all available recipes.
     List,
+    /// List recent runs in the directory of the config file.
+    History,
+    /// Rerun the last run in the directory of the config file.
+    Last,
     /// Execute the default recipe.
     Default,
-    /// Execute a recipe.
-    Recipe(String),
+    /// Execute recipes in sequence.
+    Recipes(Vec<Invocation>),
+}
+
+/// Separator between recipes to run in sequence.
+const SEPARATOR: &str = "+";
+
+/// Known options, used for suggestions on typos.
+const OPTIONS: &[&str] = &[
+    "--help",
+    "--version",
+    "--list",
+    "--history",
+    "--last",
+    "--jobs",
+    "--propagate-code",
+    "--force",
+    "--watch",
+    "--time",
+    "--echo",
+];
+
+/// Options modifying how recipes are executed.
+#[derive(Debug, Default)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "Each flag is an independent command line option"
+)]
+pub struct Options {
+    /// Number of recipes to execute concurrently, or sequentially if `None`.
+    pub jobs: Option<NonZeroUsize>,
+    /// Whether to exit with the real exit code of the last command, even if it is one of its success codes.
+    pub propagate_code: bool,
+    /// Whether to run recipes even if their outputs are up to date.
+    pub force: bool,
+    /// Whether to watch files for changes, rerunning recipes on every change.
+    pub watch: bool,
+    /// Whether to report durations of recipes and summarize the run.
+    pub time: bool,
+    /// Whether to print commands of all recipes before executing them.
+    pub echo: bool,
+}
+
+impl Options {
+    /// Checks that the options can be used together.
+    fn validate(&self) -> Result<()> {
+        if self.propagate_code && self.jobs.is_some() {
+            bail!(
+                "Option \"--propagate-code\" cannot be used with \"--jobs\", as recipes executed concurrently have no last recipe"
+            );
+        }
+        Ok(())
+    }
 }
 
 impl Action {
-    /// Parse the action from the command line arguments, removing the first argument.
-    pub fn parse(args: &mut VecDeque<String>) -> Result<Self> {
-        let first = args.pop_front();
-        let Some(first) = first.as_ref() else {
-            return Ok(Action::Default);
-        };
-        let action = match first.as_str() {
-            "--help" | "-h" => Action::Help,
-            "--version" | "-v" => Action::Version,
-            "--list" | "-l" => Action::List,
-            _ => {
-                if first.starts_with('-') {
-                    bail!("Unknown option \"{first}\"");
-                }
-                Action::Recipe(first.to_string())
+    /// Parse the action and options from the command line arguments, removing leading options, or all arguments if recipes are to be executed.
+    ///
+    /// ## Errors
+    ///
+    /// If an option is unknown, lacks its value or cannot be used with other options, or if no recipe follows a separator.
+    pub fn parse(args: &mut VecDeque<String>) -> Result<(Self, Options)> {
+        let mut options = Options::default();
+        let mut action = Self::Default;
+        while let Some(first) = args.pop_front() {
+            action = match first.as_str() {
+                "--help" | "-h" => Self::Help,
+                "--version" | "-v" => Self::Version,
+                "--list" | "-l" => Self::List,
+                "--history" => Self::History,
+                "--last" => Self::Last,
+                "--jobs" | "-j" => {
+                    let Some(jobs) = args.pop_front() else {
+                        bail!("Option \"{first}\" requires a value");
+                    };
+                    let jobs = jobs
+                        .parse()
+                        .with_context(|| format!("Invalid number of jobs \"{jobs}\""))?;
+                    options.jobs = Some(jobs);
+                    continue;
+                }
+                "--propagate-code" => {
+                    options.propagate_code = true;
+                    continue;
+                }
+                "--force" | "-f" => {
+                    options.force = true;
+                    continue;
+                }
+                "--watch" | "-w" => {
+                    options.watch = true;
+                    continue;
+                }
+                "--time" => {
+                    options.time = true;
+                    continue;
+                }
+                "--echo" | "-x" => {
+                    options.echo = true;
+                    continue;
+                }
+                _ => {
+                    if first.starts_with('-') {
+                        match crate::suggest::suggest(&first, OPTIONS.iter().copied()) {
+                            Some(option) => {
+                                bail!("Unknown option \"{first}\", did you mean \"{option}\"?")
+                            }
+                            None => bail!("Unknown option \"{first}\""),
+                        }
+                    }
+                    Self::Recipes(Self::parse_invocations(first, args)?)
+                }
+            };
+            break;
+        }
+
+        options.validate()?;
+        Ok((action, options))
+    }
+
+    /// Parse invocations separated by [`SEPARATOR`], removing all arguments.
+    fn parse_invocations(first: String, args: &mut VecDeque<String>) -> Result<Vec<Invocation>> {
+        let mut invocations = vec![Invocation::new(first, VecDeque::new())];
+        while let Some(arg) = args.pop_front() {
+            if arg != SEPARATOR {
+                if let Some(last) = invocations.last_mut() {
+                    last.args.push_back(arg);
+                }
+                continue;
             }
+            let Some(name) = args.pop_front().filter(|name| name != SEPARATOR) else {
+                bail!("Expected a recipe after \"{SEPARATOR}\"");
+            };
+            invocations.push(Invocation::new(name, VecDeque::new()));
+        }
+
+        Ok(invocations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the action and options from the given arguments.
+    fn parse(args: &[&str]) -> Result<(Action, Options)> {
+        let mut args = args.iter().map(ToString::to_string).collect();
+        Action::parse(&mut args)
+    }
+
+    #[test]
+    fn test_parse() {
+        let (action, options) = parse(&["-j", "2", "-x", "build", "--release", "+", "test"])
+            .expect("Failed to parse arguments");
+        let Action::Recipes(invocations) = action else {
+            panic!("Expected recipes, got {action:?}");
         };
+        assert_eq!(invocations.len(), 2);
+        assert_eq!(invocations[0].args, ["--release"]);
+        assert_eq!(options.jobs, NonZeroUsize::new(2));
+        assert!(options.echo);
 
-        Ok(action)
+        assert!(parse(&["--propagate-code", "build"]).is_ok());
+        assert!(parse(&["--propagate-code", "-j", "2", "build"]).is_err());
+        assert!(parse(&["-j", "2", "--propagate-code"]).is_err());
     }
 }
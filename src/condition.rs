@@ -0,0 +1,124 @@
+//! Module for conditions on which recipes are run.
+
+use serde::Deserialize;
+use std::{collections::HashMap, env, path::Path};
+
+/// Conditions that must all be met for a recipe to run, or the recipe is skipped.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Operating system to run on, as in [`std::env::consts::OS`], e.g. `linux`, `macos` or `windows`.
+    #[serde(default)]
+    pub os: Option<String>,
+    /// CPU architecture to run on, as in [`std::env::consts::ARCH`], e.g. `x86_64` or `aarch64`.
+    #[serde(default)]
+    pub arch: Option<String>,
+    /// Environment variables that must be set to the given values.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Path that must exist, relative to the working directory.
+    #[serde(default)]
+    pub exists: Option<String>,
+}
+
+impl Condition {
+    /// Checks whether all conditions are met, describing the first one that is not.
+    ///
+    /// ## Errors
+    ///
+    /// A description of the first condition not met.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(os) = &self.os
+            && os != env::consts::OS
+        {
+            return Err(format!("os is not \"{os}\""));
+        }
+        if let Some(arch) = &self.arch
+            && arch != env::consts::ARCH
+        {
+            return Err(format!("arch is not \"{arch}\""));
+        }
+        for (name, value) in &self.env {
+            if env::var(name).ok().as_ref() != Some(value) {
+                return Err(format!("env.{name} is not \"{value}\""));
+            }
+        }
+        if let Some(path) = &self.exists
+            && !Path::new(path).exists()
+        {
+            return Err(format!("\"{path}\" does not exist"));
+        }
+        Ok(())
+    }
+
+    /// Checks whether all conditions are met.
+    #[must_use]
+    pub fn is_met(&self) -> bool {
+        self.check().is_ok()
+    }
+}
+
+/// Selects the value for the current operating system from a table keyed by [`std::env::consts::OS`], [`std::env::consts::FAMILY`] or `default`, in that order.
+pub fn select_os<T>(mut table: HashMap<String, T>) -> Option<T> {
+    table
+        .remove(env::consts::OS)
+        .or_else(|| table.remove(env::consts::FAMILY))
+        .or_else(|| table.remove("default"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_condition() {
+        assert!(Condition::default().is_met());
+
+        let condition = Condition {
+            os: Some(env::consts::OS.to_string()),
+            arch: Some(env::consts::ARCH.to_string()),
+            exists: Some("Cargo.toml".to_string()),
+            ..Condition::default()
+        };
+        assert!(condition.is_met());
+
+        let condition = Condition {
+            os: Some("plan9".to_string()),
+            ..Condition::default()
+        };
+        assert_eq!(condition.check(), Err("os is not \"plan9\"".to_string()));
+
+        let condition = Condition {
+            env: HashMap::from([("JIU_SURELY_UNSET".to_string(), "true".to_string())]),
+            ..Condition::default()
+        };
+        assert_eq!(
+            condition.check(),
+            Err("env.JIU_SURELY_UNSET is not \"true\"".to_string())
+        );
+
+        let condition = Condition {
+            exists: Some("surely/missing".to_string()),
+            ..Condition::default()
+        };
+        assert!(!condition.is_met());
+    }
+
+    #[test]
+    fn test_select_os() {
+        let table = HashMap::from([(env::consts::OS.to_string(), 1), ("default".to_string(), 2)]);
+        assert_eq!(select_os(table), Some(1));
+
+        let table = HashMap::from([
+            (env::consts::FAMILY.to_string(), 1),
+            ("plan9".to_string(), 2),
+        ]);
+        assert_eq!(select_os(table), Some(1));
+
+        let table = HashMap::from([("plan9".to_string(), 1), ("default".to_string(), 2)]);
+        assert_eq!(select_os(table), Some(2));
+
+        let table = HashMap::from([("plan9".to_string(), 1)]);
+        assert_eq!(select_os(table), None);
+    }
+}
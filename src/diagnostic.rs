@@ -0,0 +1,260 @@
+//! Module for locating and rendering errors in the config file.
+
+use std::ops::Range;
+
+/// A byte range in the config file.
+pub type Span = Range<usize>;
+
+/// Spans of a recipe's arguments and command components in the config file.
+///
+/// Only populated when loading with `Config::from_toml`, and empty otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecipeSpans {
+    /// Spans of [`Recipe::arguments`](crate::Recipe::arguments), in the same order.
+    pub arguments: Vec<Span>,
+    /// Spans of [`Recipe::command`](crate::Recipe::command), in the same order.
+    pub command: Vec<Span>,
+}
+
+/// Renders a diagnostic message pointing at the span in the source, with a code excerpt and caret.
+///
+/// ## Example
+///
+/// ```text
+/// Argument "x" defined as Required but referenced as ?Optional at .jiu.toml:4:20
+///   |
+/// 4 | command = ["echo", ["?x"]]
+///   |                    ^^^^^^
+/// ```
+#[must_use]
+pub fn render(source: &str, path: &str, span: &Span, message: &str) -> String {
+    // Find the line containing the start of the span
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = source[..start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count() + 1;
+
+    // Underline the span, clamped to the line
+    let end = span.end.clamp(start, line_end);
+    let caret_len = source[start..end].chars().count().max(1);
+    let excerpt = source[line_start..line_end].trim_end_matches('\r');
+
+    let gutter = " ".repeat(line.to_string().len());
+    let indent = " ".repeat(column - 1);
+    let carets = "^".repeat(caret_len);
+    format!(
+        "{message} at {path}:{line}:{column}\n{gutter} |\n{line} | {excerpt}\n{gutter} | {indent}{carets}"
+    )
+}
+
+/// Collects the spans of the recipes, rejecting those with a string `command` but no `shell`.
+///
+/// ## Errors
+///
+/// If the source is not a valid configuration.
+#[cfg(feature = "toml")]
+pub(crate) fn recipe_spans(source: &str) -> Result<Vec<RecipeSpans>, toml::de::Error> {
+    let repr: repr::ConfigRepr = toml::from_str(source)?;
+    Ok(repr
+        .recipes
+        .into_iter()
+        .map(|repr::RecipeRepr(repr)| RecipeSpans {
+            arguments: repr.arguments.iter().map(toml::Spanned::span).collect(),
+            command: repr.command.spans,
+        })
+        .collect())
+}
+
+/// Representations of the config file holding spans, deserialized separately from the config itself.
+#[cfg(feature = "toml")]
+mod repr {
+    use super::Span;
+    use crate::{Error, condition};
+    use serde::{Deserialize, de::IgnoredAny};
+    use std::collections::HashMap;
+    use toml::Spanned;
+
+    /// Spans of the recipes, deserialized separately from the recipes themselves.
+    #[derive(Deserialize)]
+    pub(super) struct ConfigRepr {
+        #[serde(default)]
+        pub(super) recipes: Vec<RecipeRepr>,
+    }
+
+    /// Spans of a recipe, checked as soon as they are deserialized so that errors point at it.
+    pub(super) struct RecipeRepr(pub(super) RecipeFields);
+
+    impl<'de> Deserialize<'de> for RecipeRepr {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_newtype_struct("RecipeRepr", RecipeVisitor)
+        }
+    }
+
+    /// Visitor rejecting script strings outside shell mode, which only the source tells apart from single literals.
+    pub(super) struct RecipeVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for RecipeVisitor {
+        type Value = RecipeRepr;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a recipe")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let fields = RecipeFields::deserialize(deserializer)?;
+            if fields.command.script && fields.shell.is_none() {
+                return Err(serde::de::Error::custom(Error::InvalidRecipe {
+                    name: fields.names.first().cloned().unwrap_or_default(),
+                    message: "a string `command` requires `shell`".to_string(),
+                }));
+            }
+            Ok(RecipeRepr(fields))
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct RecipeFields {
+        #[serde(default)]
+        names: Vec<String>,
+        #[serde(default)]
+        shell: Option<IgnoredAny>,
+        #[serde(default)]
+        pub(super) arguments: Vec<Spanned<IgnoredAny>>,
+        #[serde(default)]
+        pub(super) command: CommandRepr,
+    }
+
+    /// Spans of the components of a command, or nothing if the command is a script string without placeholders to locate.
+    #[derive(Default)]
+    pub(super) struct CommandRepr {
+        pub(super) spans: Vec<Span>,
+        script: bool,
+    }
+
+    impl<'de> Deserialize<'de> for CommandRepr {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct CommandVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for CommandVisitor {
+                type Value = CommandRepr;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    write!(
+                        f,
+                        "a list of components, a script string or a table of them"
+                    )
+                }
+
+                fn visit_str<E: serde::de::Error>(self, _: &str) -> Result<Self::Value, E> {
+                    Ok(CommandRepr {
+                        spans: Vec::new(),
+                        script: true,
+                    })
+                }
+
+                fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                    self,
+                    mut seq: A,
+                ) -> Result<Self::Value, A::Error> {
+                    let mut spans = Vec::new();
+                    while let Some(component) = seq.next_element::<Spanned<IgnoredAny>>()? {
+                        spans.push(component.span());
+                    }
+                    Ok(CommandRepr {
+                        spans,
+                        script: false,
+                    })
+                }
+
+                fn visit_map<A: serde::de::MapAccess<'de>>(
+                    self,
+                    mut map: A,
+                ) -> Result<Self::Value, A::Error> {
+                    let mut table = HashMap::new();
+                    while let Some((os, command)) = map.next_entry::<String, CommandRepr>()? {
+                        table.insert(os, command);
+                    }
+                    // A missing operating system is rejected when deserializing the config itself
+                    Ok(condition::select_os(table).unwrap_or_default())
+                }
+            }
+
+            deserializer.deserialize_any(CommandVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::collections::VecDeque;
+
+    const SOURCE: &str = r#"[[recipes]]
+names = ["test"]
+arguments = ["arg0", "arg1"]
+command = ["echo", ["arg0"], ["?arg1"]]
+"#;
+
+    #[test]
+    fn test_locate_and_render() {
+        let mut config: Config = toml::from_str(SOURCE).unwrap();
+        let recipe = &mut config.recipes[0];
+        recipe.spans = RecipeSpans {
+            arguments: vec![42..48, 50..56],
+            command: vec![69..75, 77..85, 87..96],
+        };
+
+        let err = recipe
+            .resolve(VecDeque::from(vec!["a".to_string(), "b".to_string()]))
+            .unwrap_err();
+        let span = recipe.locate(&err).unwrap();
+        assert_eq!(
+            render(SOURCE, ".jiu.toml", &span, &err.to_string()),
+            r#"Argument "arg1" defined as Required but referenced as ?Optional at .jiu.toml:4:30
+  |
+4 | command = ["echo", ["arg0"], ["?arg1"]]
+  |                              ^^^^^^^^^"#
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(SOURCE).unwrap();
+        let spans = &config.recipes[0].spans;
+        assert_eq!(spans.arguments, vec![42..48, 50..56]);
+        assert_eq!(spans.command, vec![69..75, 77..85, 87..96]);
+        assert_eq!(&SOURCE[spans.command[2].clone()], r#"["?arg1"]"#);
+
+        // Scripts have no placeholders to locate, and tables span the command for the current operating system
+        let source = r#"
+            [[recipes]]
+            names = ["script"]
+            shell = "sh"
+            command = "echo $1"
+
+            [[recipes]]
+            names = ["table"]
+            command.plan9 = ["plumb"]
+            command.default = ["xdg-open", ["?page"]]
+        "#;
+        let config = Config::from_toml(source).unwrap();
+        assert!(config.recipes[0].spans.command.is_empty());
+        let spans = &config.recipes[1].spans.command;
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&source[spans[1].clone()], r#"["?page"]"#);
+    }
+}
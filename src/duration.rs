@@ -0,0 +1,79 @@
+//! Module for parsing human-readable durations like `1m30s`.
+
+use serde::{Deserialize, Deserializer, de::Error};
+use std::time::Duration;
+
+/// Parses a duration made up of numbers with units, e.g. `500ms`, `10m` or `1h30m`.
+///
+/// Supported units are `ms`, `s`, `m` and `h`.
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let mut total = Duration::ZERO;
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("Empty duration".to_string());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("Expected a number in duration \"{s}\""))?;
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let secs = |factor: u64| value.checked_mul(factor).map(Duration::from_secs);
+        let duration = match &rest[..unit] {
+            "ms" => Some(Duration::from_millis(value)),
+            "s" => Some(Duration::from_secs(value)),
+            "m" => secs(60),
+            "h" => secs(60 * 60),
+            "" => return Err(format!("Missing unit in duration \"{s}\"")),
+            other => return Err(format!("Unknown unit \"{other}\" in duration \"{s}\"")),
+        };
+        total = duration
+            .and_then(|duration| total.checked_add(duration))
+            .ok_or_else(|| format!("Duration \"{s}\" is too long"))?;
+        rest = &rest[unit..];
+    }
+    Ok(total)
+}
+
+/// Deserializes an optional duration from a string, see [`parse`].
+pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| parse(&s).map_err(D::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse("10m"), Ok(Duration::from_mins(10)));
+        assert_eq!(parse("1h30m15s"), Ok(Duration::from_secs(5415)));
+        assert_eq!(
+            parse("10"),
+            Err("Missing unit in duration \"10\"".to_string())
+        );
+        assert_eq!(
+            parse("3d"),
+            Err("Unknown unit \"d\" in duration \"3d\"".to_string())
+        );
+        assert_eq!(
+            parse("18446744073709551615s1s"),
+            Err("Duration \"18446744073709551615s1s\" is too long".to_string())
+        );
+        assert!(parse("18446744073709551615h").is_err());
+        assert!(parse("").is_err());
+        assert!(parse("m").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Module for errors that library users may want to match on.
+
+use crate::arguments::ArgumentType;
+use std::{env::VarError, fmt::Display};
+
+/// Errors when parsing, looking up or resolving recipes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// An argument definition or placeholder is empty.
+    EmptyArgument,
+    /// A required argument is not provided.
+    MissingArgument {
+        /// Name of the argument.
+        name: String,
+        /// Type of the argument, either [`ArgumentType::Required`] or [`ArgumentType::RequiredVariadic`].
+        arg_type: ArgumentType,
+    },
+    /// Arguments are left after resolving.
+    UnexpectedArguments(Vec<String>),
+    /// A placeholder references an argument that is not defined.
+    UndefinedPlaceholder {
+        /// Name of the referenced argument.
+        name: String,
+    },
+    /// A placeholder references an argument as a type different from its definition.
+    TypeMismatch {
+        /// Name of the argument.
+        name: String,
+        /// Type of the argument in its definition.
+        defined: ArgumentType,
+        /// Type of the argument in the placeholder.
+        referenced: ArgumentType,
+    },
+    /// A referenced environment variable is not set or not valid unicode.
+    EnvVarMissing {
+        /// Name of the environment variable.
+        name: String,
+        /// The underlying error.
+        source: VarError,
+    },
+    /// A referenced captured value has not been captured by an earlier recipe.
+    CaptureMissing {
+        /// Name of the captured value.
+        name: String,
+    },
+    /// A placeholder is used in the script of a recipe in shell mode.
+    ScriptPlaceholder {
+        /// The placeholder as written in the config file.
+        placeholder: String,
+    },
+    /// A command substitution could not be executed or failed.
+    SubstitutionFailed {
+        /// The substituted command.
+        command: Vec<String>,
+        /// Description of the failure, including standard error of the command if any.
+        message: String,
+    },
+    /// The fields of a recipe cannot be used together, or it has nothing to run.
+    InvalidRecipe {
+        /// First name of the recipe.
+        name: String,
+        /// Description of the problem.
+        message: String,
+    },
+    /// No recipe is found with the given name.
+    RecipeNotFound {
+        /// The name used to look up the recipe.
+        name: String,
+    },
+    /// The prefix used to look up the recipe matches multiple recipes.
+    AmbiguousPrefix {
+        /// The prefix used to look up the recipe.
+        prefix: String,
+        /// Names of the recipes starting with the prefix.
+        candidates: Vec<String>,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyArgument => write!(f, "Empty argument"),
+            Self::MissingArgument {
+                arg_type: ArgumentType::RequiredVariadic,
+                ..
+            } => write!(
+                f,
+                "Required variadic argument must contain at least one value"
+            ),
+            Self::MissingArgument { .. } => write!(f, "Required argument not provided"),
+            Self::UnexpectedArguments(args) => write!(f, "Unexpected argument(s): {args:?}"),
+            Self::UndefinedPlaceholder { name } => write!(f, "Argument {name} not found"),
+            Self::TypeMismatch {
+                name,
+                defined,
+                referenced,
+            } => write!(
+                f,
+                "Argument \"{name}\" defined as {defined} but referenced as {referenced}"
+            ),
+            Self::EnvVarMissing { source, .. } => write!(f, "{source}"),
+            Self::CaptureMissing { name } => write!(
+                f,
+                "Captured value \"{name}\" not found, it must be captured by an earlier recipe"
+            ),
+            Self::ScriptPlaceholder { placeholder } => write!(
+                f,
+                "Placeholder {placeholder} is not allowed in shell scripts, use positional parameters like \"$1\" instead"
+            ),
+            Self::SubstitutionFailed { command, message } => {
+                write!(f, "Command substitution {command:?} failed: {message}")
+            }
+            Self::InvalidRecipe { name, message } => {
+                write!(f, "Recipe \"{name}\" is invalid: {message}")
+            }
+            Self::RecipeNotFound { name } => write!(f, "Recipe \"{name}\" not found"),
+            Self::AmbiguousPrefix { prefix, candidates } => write!(
+                f,
+                "Recipe prefix \"{prefix}\" is ambiguous, candidates: {}",
+                candidates.join(", ")
+            ),
+        }
+    }
+}
+
+// The underlying error of `EnvVarMissing` is not returned as the source, since it is already part of the message
+impl std::error::Error for Error {}
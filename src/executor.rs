@@ -0,0 +1,540 @@
+//! Module for executing resolved commands.
+
+use crate::Freshness;
+use anyhow::{Context, Result, bail};
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    env,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    hash::{BuildHasher, RandomState},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{self, Child, Command, ExitStatus, Stdio},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The exit status of an executed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The command exited with the given code.
+    Exited(i32),
+    /// The command was terminated by the given signal.
+    Signaled(i32),
+    /// The command was terminated without an exit code.
+    Terminated,
+    /// The command was terminated after timing out.
+    TimedOut,
+    /// The command was terminated as its job was cancelled, see [`Cancel`].
+    Cancelled,
+    /// The command was not executed, as its outputs are up to date, see [`Job::freshness`].
+    ///
+    /// Counts as success, exiting with code `0`.
+    UpToDate,
+}
+
+impl Status {
+    /// Whether the command exited successfully, or did not need executing.
+    #[must_use]
+    pub const fn success(self) -> bool {
+        matches!(self, Self::Exited(0) | Self::UpToDate)
+    }
+
+    /// The exit code to propagate, `128 + signal` if the command was signaled, `124` if timed out like `timeout(1)`, `130` if cancelled like interrupted by Ctrl-C, or `1` if the command was terminated otherwise.
+    #[must_use]
+    pub const fn code(self) -> i32 {
+        match self {
+            Self::Exited(code) => code,
+            Self::Signaled(signal) => 128 + signal,
+            Self::Terminated => 1,
+            Self::TimedOut => 124,
+            Self::Cancelled => 130,
+            Self::UpToDate => 0,
+        }
+    }
+}
+
+impl From<ExitStatus> for Status {
+    fn from(status: ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Self::Signaled(signal);
+            }
+        }
+        status.code().map_or(Self::Terminated, Self::Exited)
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exit status: {code}"),
+            Self::Signaled(signal) => write!(f, "signal: {signal}"),
+            Self::Terminated => write!(f, "termination without exit code"),
+            Self::TimedOut => write!(f, "timeout"),
+            Self::Cancelled => write!(f, "cancellation"),
+            Self::UpToDate => write!(f, "outputs up to date"),
+        }
+    }
+}
+
+/// A resolved command with options on how to execute it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Job {
+    /// The command to execute, program first, or arguments to [`Job::script`] if set.
+    pub command: Vec<String>,
+    /// Prefix for each line of output, used when executing multiple jobs concurrently.
+    pub prefix: Option<String>,
+    /// Time after which the command is terminated.
+    pub timeout: Option<Duration>,
+    /// Number of times to rerun the command after it fails.
+    pub retries: u32,
+    /// Time to wait before rerunning the command.
+    pub retry_delay: Duration,
+    /// Exit codes treated as success, or only `0` if empty.
+    pub success_codes: Vec<i32>,
+    /// Inline script to execute, taking the command as arguments.
+    pub script: Option<Script>,
+    /// Name to capture the trimmed standard output of the command as, see [`Executor::capture`].
+    pub capture: Option<String>,
+    /// Sources and outputs of the command, to skip it if its outputs are up to date.
+    pub freshness: Option<Freshness>,
+    /// Flag to cancel the job from another thread, terminating its command.
+    pub cancel: Option<Cancel>,
+    /// Whether to print the command before executing it, see [`Job::command_line`].
+    pub echo: bool,
+}
+
+impl Job {
+    /// Creates a new job executing the command with default options.
+    #[must_use]
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            command,
+            ..Self::default()
+        }
+    }
+
+    /// Renders the command like it would be typed in a POSIX shell, quoting arguments where needed.
+    ///
+    /// For inline scripts, the script is shown as `<script>` after the interpreter if any, followed by the arguments.
+    #[must_use]
+    pub fn command_line(&self) -> String {
+        let script = self.script.as_ref().map(|script| {
+            let interpreter = script.interpreter.as_deref().map(quote);
+            interpreter.into_iter().chain([Cow::Borrowed("<script>")])
+        });
+        let words: Vec<Cow<str>> = script
+            .into_iter()
+            .flatten()
+            .chain(self.command.iter().map(|arg| quote(arg)))
+            .collect();
+        words.join(" ")
+    }
+
+    /// Whether the status of the command counts as success, taking [`Job::success_codes`] into account.
+    #[must_use]
+    pub fn accepts(&self, status: Status) -> bool {
+        match status {
+            Status::Exited(code) if !self.success_codes.is_empty() => {
+                self.success_codes.contains(&code)
+            }
+            _ => status.success(),
+        }
+    }
+}
+
+/// A flag to cancel jobs from another thread, shared by its clones.
+///
+/// Running commands of cancelled jobs are terminated like on timeout, with [`Status::Cancelled`].
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    /// Cancels the jobs holding this flag.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Clears the flag, so that jobs holding it can run again.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the jobs holding this flag are cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Flags are equal if they are clones of each other.
+impl PartialEq for Cancel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Cancel {}
+
+/// An inline script, written to a temporary file to execute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    /// Body of the script.
+    pub body: String,
+    /// Interpreter to run the script file with, or `None` to execute the file directly.
+    pub interpreter: Option<String>,
+}
+
+/// Executes jobs.
+///
+/// Implement this trait to plug in a custom backend, e.g. running commands in a container.
+pub trait Executor {
+    /// Executes the job, waiting for it to finish.
+    ///
+    /// ## Errors
+    ///
+    /// If the command could not be executed.
+    fn execute(&self, job: &Job) -> Result<Status>;
+
+    /// Executes the job, waiting for it to finish and returning its standard output instead of forwarding it.
+    ///
+    /// ## Errors
+    ///
+    /// If the command could not be executed, or capturing output is not supported by the executor.
+    fn capture(&self, job: &Job) -> Result<(Status, String)> {
+        let _ = job;
+        bail!("Capturing output is not supported by this executor")
+    }
+}
+
+/// Quotes the argument for POSIX shells if it contains special characters, so that it reads as a single word.
+#[must_use]
+pub fn quote(arg: &str) -> Cow<'_, str> {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        Cow::Borrowed(arg)
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+    }
+}
+
+/// Executes jobs by spawning child processes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessExecutor;
+
+/// Time to wait for a command to exit after asking it to terminate, before killing it.
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Interval to check whether a command with timeout or cancel flag has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Executor for ProcessExecutor {
+    fn execute(&self, job: &Job) -> Result<Status> {
+        Self::spawn(job, false).map(|(status, _)| status)
+    }
+
+    fn capture(&self, job: &Job) -> Result<(Status, String)> {
+        Self::spawn(job, true)
+    }
+}
+
+impl ProcessExecutor {
+    /// Spawns the command and waits for it to finish, capturing standard output if requested.
+    fn spawn(job: &Job, capture: bool) -> Result<(Status, String)> {
+        let command = &job.command;
+        // The script file is kept until the command finishes
+        let script_file = job
+            .script
+            .as_ref()
+            .map(|script| ScriptFile::create(&script.body))
+            .transpose()
+            .context("Error writing script to a temporary file")?;
+        let mut cmd = if let (Some(script), Some(file)) = (&job.script, &script_file) {
+            let mut cmd = script.interpreter.as_ref().map_or_else(
+                || Command::new(&file.0),
+                |interpreter| {
+                    let mut cmd = Command::new(interpreter);
+                    cmd.arg(&file.0);
+                    cmd
+                },
+            );
+            cmd.args(command);
+            cmd
+        } else {
+            let Some((program, args)) = command.split_first() else {
+                bail!("Empty command");
+            };
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        };
+        if capture || job.prefix.is_some() {
+            cmd.stdout(Stdio::piped());
+        }
+        if job.prefix.is_some() {
+            cmd.stderr(Stdio::piped());
+        }
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Error spawning command \"{command:?}\""))?;
+        #[cfg(unix)]
+        let _guard = crate::signals::ChildGuard::new(child.id());
+
+        // Forward output line by line until the pipes are closed
+        let prefix = job.prefix.as_deref().unwrap_or_default();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let mut output = Vec::new();
+        let status = thread::scope(|scope| {
+            if let Some(mut stdout) = stdout {
+                if capture {
+                    // Read errors only cut the output short, like closed pipes
+                    let output = &mut output;
+                    scope.spawn(move || stdout.read_to_end(output));
+                } else {
+                    scope.spawn(|| forward(stdout, || io::stdout().lock(), prefix));
+                }
+            }
+            if let Some(stderr) = stderr {
+                scope.spawn(|| forward(stderr, || io::stderr().lock(), prefix));
+            }
+            if job.timeout.is_none() && job.cancel.is_none() {
+                child.wait().map(Status::from)
+            } else {
+                wait_until(&mut child, job.timeout, job.cancel.as_ref())
+            }
+        });
+        let status =
+            status.with_context(|| format!("Error waiting for command \"{command:?}\""))?;
+        Ok((status, String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+#[cfg(unix)]
+impl ProcessExecutor {
+    /// Replaces the current process with the command, keeping the process ID, signal handling and terminal.
+    ///
+    /// ## Errors
+    ///
+    /// If the command could not be executed, in which case the current process is kept.
+    pub fn exec(&self, command: &[String]) -> Result<Infallible> {
+        use std::os::unix::process::CommandExt;
+
+        let Some((program, args)) = command.split_first() else {
+            bail!("Empty command");
+        };
+        let err = Command::new(program).args(args).exec();
+        Err(err).with_context(|| format!("Error executing command \"{command:?}\""))
+    }
+}
+
+/// Waits for the child process to finish, terminating it after the timeout or once cancelled.
+fn wait_until(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    cancel: Option<&Cancel>,
+) -> io::Result<Status> {
+    // Timeouts too long to represent are never reached
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status.into());
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break Status::TimedOut;
+        }
+        if cancel.is_some_and(Cancel::is_cancelled) {
+            break Status::Cancelled;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    terminate(child)?;
+    Ok(status)
+}
+
+/// Terminates the child process, asking it to terminate first, and killing it if it does not exit within [`GRACE_PERIOD`].
+fn terminate(child: &mut Child) -> io::Result<()> {
+    // Ask the child to terminate
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` has no memory safety requirements
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+        let deadline = Instant::now() + GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // Kill the child if it is still running
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+/// A temporary file holding a script, removed when dropped.
+struct ScriptFile(PathBuf);
+
+impl ScriptFile {
+    /// Writes the script to a new temporary file, executable by the owner on Unix.
+    ///
+    /// The file name is unpredictable, and another one is tried if it exists, so that files planted in the shared temporary directory are never written to or executed.
+    fn create(body: &str) -> io::Result<Self> {
+        /// Number of names to try before giving up.
+        const ATTEMPTS: usize = 16;
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o700);
+        let mut attempts = 1;
+        let (mut file, path) = loop {
+            let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+            // Randomly keyed hashers are the only source of randomness in the standard library
+            let random = RandomState::new().hash_one(count);
+            let path = env::temp_dir().join(format!("jiu-{}-{count}-{random:016x}", process::id()));
+            match options.open(&path) {
+                Ok(file) => break (file, path),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempts < ATTEMPTS => {
+                    attempts += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        // Removed by the guard even if writing fails
+        let script = Self(path);
+        file.write_all(body.as_bytes())?;
+        Ok(script)
+    }
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Forwards output from the reader to the writer, prefixing each line.
+///
+/// The writer is locked for each line, so that lines from concurrent commands are not interleaved.
+fn forward<W: Write>(reader: impl Read, writer: impl Fn() -> W, prefix: &str) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        let mut writer = writer();
+        // Ignore write errors, e.g. when stdout is closed
+        let _ = writer
+            .write_all(prefix.as_bytes())
+            .and_then(|()| writer.write_all(&line))
+            .and_then(|()| writer.flush());
+    }
+}
+
+/// Records commands instead of executing them, useful for testing.
+#[derive(Debug)]
+pub struct RecordingExecutor {
+    /// The recorded commands.
+    commands: Mutex<Vec<Vec<String>>>,
+    /// The status to report for every command.
+    status: Status,
+    /// The output to report for every captured command.
+    output: String,
+}
+
+impl Default for RecordingExecutor {
+    fn default() -> Self {
+        Self::new(Status::Exited(0))
+    }
+}
+
+impl RecordingExecutor {
+    /// Creates a new recording executor, reporting the given status for every command.
+    #[must_use]
+    pub const fn new(status: Status) -> Self {
+        Self {
+            commands: Mutex::new(Vec::new()),
+            status,
+            output: String::new(),
+        }
+    }
+
+    /// Sets the output to report for every captured command.
+    #[must_use]
+    pub fn with_output(mut self, output: impl Into<String>) -> Self {
+        self.output = output.into();
+        self
+    }
+
+    /// Gets the commands recorded so far.
+    ///
+    /// ## Panics
+    ///
+    /// If the lock is poisoned.
+    #[must_use]
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.commands.lock().expect("Lock poisoned").clone()
+    }
+}
+
+impl Executor for RecordingExecutor {
+    fn execute(&self, job: &Job) -> Result<Status> {
+        self.commands
+            .lock()
+            .expect("Lock poisoned")
+            .push(job.command.clone());
+        Ok(self.status)
+    }
+
+    fn capture(&self, job: &Job) -> Result<(Status, String)> {
+        let status = self.execute(job)?;
+        Ok((status, self.output.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("cargo"), "cargo");
+        assert_eq!(quote("--out=dist/app.js"), "--out=dist/app.js");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn test_script_file() {
+        let first = ScriptFile::create("echo first").unwrap();
+        let second = ScriptFile::create("echo second").unwrap();
+        assert_ne!(first.0, second.0);
+        assert_eq!(fs::read_to_string(&first.0).unwrap(), "echo first");
+
+        let path = first.0.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,310 @@
+//! Module for checking whether outputs of recipes are up to date with their sources.
+
+use crate::executor::Job;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Directory to store hashes of sources in by default, relative to the working directory.
+const CACHE_DIR: &str = ".jiu/cache";
+
+/// How to check whether outputs of a recipe are up to date.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UpToDate {
+    /// Outputs are up to date if none of them is older than any source.
+    #[default]
+    Mtime,
+    /// Outputs are up to date if they exist, and the hash of the sources and the command is unchanged since the last successful run.
+    Hash,
+}
+
+/// Sources and outputs of a job, to skip the job if its outputs are up to date.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Freshness {
+    /// Name to store the hash under, the first name of the recipe.
+    pub name: String,
+    /// Glob patterns of files the outputs are generated from.
+    pub sources: Vec<String>,
+    /// Glob patterns of files generated by the job, each of which must match a file.
+    pub outputs: Vec<String>,
+    /// How to check whether outputs are up to date.
+    pub method: UpToDate,
+    /// Directory to store hashes of sources in, or `.jiu/cache` in the working directory if `None`.
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Freshness {
+    /// Checks whether the outputs of the job are up to date with its sources.
+    ///
+    /// ## Errors
+    ///
+    /// If a pattern is invalid, or a matched file could not be read.
+    pub fn is_up_to_date(&self, job: &Job) -> Result<bool> {
+        let mut outputs = Vec::new();
+        for pattern in &self.outputs {
+            let matched = expand(pattern)?;
+            if matched.is_empty() {
+                return Ok(false);
+            }
+            outputs.extend(matched);
+        }
+        let sources = self.expand_sources()?;
+        match self.method {
+            UpToDate::Mtime => {
+                if outputs.is_empty() {
+                    return Ok(false);
+                }
+                let newest_source = modified_times(&sources)?.into_iter().max();
+                let oldest_output = modified_times(&outputs)?.into_iter().min();
+                Ok(newest_source <= oldest_output)
+            }
+            UpToDate::Hash => {
+                let Ok(stored) = fs::read_to_string(self.cache_path()) else {
+                    return Ok(false);
+                };
+                Ok(stored.trim() == hash(job, &sources)?)
+            }
+        }
+    }
+
+    /// Records the hash of the sources after the job succeeded, if checking by [`UpToDate::Hash`].
+    ///
+    /// ## Errors
+    ///
+    /// If a pattern is invalid, a source could not be read, or the hash could not be written.
+    pub fn record(&self, job: &Job) -> Result<()> {
+        if self.method != UpToDate::Hash {
+            return Ok(());
+        }
+        let digest = hash(job, &self.expand_sources()?)?;
+        let cache_dir = self.cache_dir();
+        fs::create_dir_all(cache_dir).with_context(|| {
+            format!("Error creating cache directory \"{}\"", cache_dir.display())
+        })?;
+        let path = self.cache_path();
+        fs::write(&path, digest)
+            .with_context(|| format!("Error writing cache file \"{}\"", path.display()))
+    }
+
+    /// Expands the source patterns to files, sorted so that hashes do not depend on the order of matches.
+    fn expand_sources(&self) -> Result<Vec<PathBuf>> {
+        let mut sources = Vec::new();
+        for pattern in &self.sources {
+            sources.extend(expand(pattern)?);
+        }
+        sources.sort();
+        sources.dedup();
+        Ok(sources)
+    }
+
+    /// Path to the file storing the hash, with characters other than alphanumerics, `-`, `_` and `.` in the name replaced.
+    fn cache_path(&self) -> PathBuf {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.cache_dir().join(name)
+    }
+
+    /// Directory to store hashes of sources in, see [`Freshness::cache_dir`].
+    fn cache_dir(&self) -> &Path {
+        self.cache_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new(CACHE_DIR))
+    }
+}
+
+/// Expands the glob pattern to the files it matches.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).with_context(|| format!("Invalid pattern \"{pattern}\""))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.with_context(|| format!("Error matching pattern \"{pattern}\""))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Gets the modification times of the files.
+fn modified_times(paths: &[PathBuf]) -> Result<Vec<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .with_context(|| {
+                    format!("Error reading modification time of \"{}\"", path.display())
+                })
+        })
+        .collect()
+}
+
+/// Hashes the command, the inline script and the paths and contents of the sources.
+///
+/// Each field is prefixed with its length, so that moving bytes between adjacent fields changes the hash.
+fn hash(job: &Job, sources: &[PathBuf]) -> Result<String> {
+    let mut hasher = Fnv::new();
+    hasher.write_len(job.command.len());
+    for word in &job.command {
+        hasher.write_field(word.as_bytes());
+    }
+    if let Some(script) = &job.script {
+        hasher.write_field(script.body.as_bytes());
+        hasher.write_field(script.interpreter.as_deref().unwrap_or_default().as_bytes());
+    }
+    hasher.write_len(sources.len());
+    for source in sources {
+        hasher.write_field(source.as_os_str().as_encoded_bytes());
+        let content =
+            fs::read(source).with_context(|| format!("Error reading \"{}\"", source.display()))?;
+        hasher.write_field(&content);
+    }
+    Ok(format!("{:016x}", hasher.0))
+}
+
+/// 64-bit FNV-1a hasher, which unlike the hashers of the standard library is stable across Rust versions and platforms.
+struct Fnv(u64);
+
+impl Fnv {
+    /// Offset basis of 64-bit FNV-1a.
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    /// Prime of 64-bit FNV-1a.
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    /// Creates a hasher with nothing written.
+    const fn new() -> Self {
+        Self(Self::OFFSET)
+    }
+
+    /// Writes the bytes.
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Writes a length as 8 little-endian bytes, regardless of the width of `usize`.
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    /// Writes the bytes prefixed with their length.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write_len(bytes.len());
+        self.write(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs::File, process, time::Duration};
+
+    #[test]
+    fn test_hash() {
+        let dir = env::temp_dir().join(format!("jiu-test-hash-{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let source = dir.join("source.in");
+        fs::write(&source, "one").expect("Failed to write source");
+        let cache_dir = dir.join("cache");
+        let freshness = Freshness {
+            name: "test/hash".to_string(),
+            sources: vec![format!("{}/*.in", dir.display())],
+            method: UpToDate::Hash,
+            cache_dir: Some(cache_dir.clone()),
+            ..Freshness::default()
+        };
+        assert_eq!(freshness.cache_path(), cache_dir.join("test_hash"));
+        assert!(Freshness::default().cache_path().starts_with(CACHE_DIR));
+
+        let job = Job::new(vec!["generate".to_string()]);
+        assert!(!freshness.is_up_to_date(&job).expect("Failed to check"));
+        freshness.record(&job).expect("Failed to record");
+        assert!(freshness.is_up_to_date(&job).expect("Failed to check"));
+
+        // Changed command or sources
+        let other = Job::new(vec!["generate".to_string(), "--all".to_string()]);
+        assert!(!freshness.is_up_to_date(&other).expect("Failed to check"));
+        fs::write(&source, "two").expect("Failed to write source");
+        assert!(!freshness.is_up_to_date(&job).expect("Failed to check"));
+
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+    }
+
+    #[test]
+    fn test_fnv() {
+        // Reference values of 64-bit FNV-1a, so that stored hashes stay valid
+        let digest = |bytes: &[u8]| {
+            let mut hasher = Fnv::new();
+            hasher.write(bytes);
+            hasher.0
+        };
+        assert_eq!(digest(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(digest(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(digest(b"foobar"), 0x8594_4171_f739_67e8);
+
+        let job = Job::new(vec!["generate".to_string()]);
+        assert_ne!(
+            hash(&job, &[]).unwrap(),
+            hash(&Job::new(vec!["gene".to_string(), "rate".to_string()]), &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mtime() {
+        let dir = env::temp_dir().join(format!("jiu-test-mtime-{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let source = dir.join("source.in");
+        let output = dir.join("output.out");
+        fs::write(&source, "").expect("Failed to write source");
+        fs::write(&output, "").expect("Failed to write output");
+        let freshness = Freshness {
+            sources: vec![format!("{}/*.in", dir.display())],
+            outputs: vec![format!("{}/*.out", dir.display())],
+            ..Freshness::default()
+        };
+        let job = Job::default();
+
+        let set_modified = |path: &PathBuf, time: SystemTime| {
+            File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(time))
+                .expect("Failed to set modification time");
+        };
+        let now = SystemTime::now();
+        set_modified(&source, now - Duration::from_mins(1));
+        set_modified(&output, now);
+        assert!(freshness.is_up_to_date(&job).expect("Failed to check"));
+
+        set_modified(&source, now + Duration::from_mins(1));
+        assert!(!freshness.is_up_to_date(&job).expect("Failed to check"));
+
+        // Missing outputs
+        fs::remove_file(&output).expect("Failed to remove output");
+        assert!(!freshness.is_up_to_date(&job).expect("Failed to check"));
+
+        let invalid = Freshness {
+            sources: vec!["[".to_string()],
+            outputs: vec![format!("{}/*.in", dir.display())],
+            ..Freshness::default()
+        };
+        assert!(invalid.is_up_to_date(&job).is_err());
+
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+    }
+}
@@ -0,0 +1,266 @@
+//! Module for recording runs, so that they can be listed and rerun.
+
+use crate::{Invocation, executor::quote};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Maximum number of entries to keep, with older entries removed once twice as many are recorded.
+const MAX_ENTRIES: usize = 1000;
+
+/// Separator between invocations in a recorded run, as on the command line.
+const SEPARATOR: &str = "+";
+
+/// A recorded run of recipes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Time the run started.
+    pub started: SystemTime,
+    /// Time the run took.
+    pub duration: Duration,
+    /// Directory of the config file the recipes were run from.
+    pub directory: PathBuf,
+    /// Invocations of the run, in order.
+    pub invocations: Vec<Invocation>,
+    /// Code the run exited with, or `None` if `jiu` was replaced by the command.
+    pub code: Option<i32>,
+}
+
+impl Entry {
+    /// Renders the invocations like they would be typed on the command line, quoting arguments where needed.
+    #[must_use]
+    pub fn command_line(&self) -> String {
+        let words: Vec<Cow<str>> = self
+            .invocations
+            .iter()
+            .enumerate()
+            .flat_map(|(i, Invocation { name, args })| {
+                let separator = (i > 0).then_some(Cow::Borrowed(SEPARATOR));
+                separator
+                    .into_iter()
+                    .chain(std::iter::once(quote(name)))
+                    .chain(args.iter().map(|arg| quote(arg)))
+            })
+            .collect();
+        words.join(" ")
+    }
+
+    /// Serializes the entry to a line of tab-separated fields, with invocations flattened as on the command line.
+    fn to_line(&self) -> String {
+        let started = self
+            .started
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let code = self
+            .code
+            .map_or_else(|| "-".to_string(), |code| code.to_string());
+        let mut fields = vec![
+            started.to_string(),
+            self.duration.as_millis().to_string(),
+            code,
+            escape(&self.directory.to_string_lossy()),
+        ];
+        for (i, Invocation { name, args }) in self.invocations.iter().enumerate() {
+            if i > 0 {
+                fields.push(SEPARATOR.to_string());
+            }
+            fields.push(escape(name));
+            fields.extend(args.iter().map(|arg| escape(arg)));
+        }
+        fields.join("\t")
+    }
+
+    /// Parses an entry from a line, or `None` if it is malformed.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let started = SystemTime::UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+        let duration = Duration::from_millis(fields.next()?.parse().ok()?);
+        let code = match fields.next()? {
+            "-" => None,
+            code => Some(code.parse().ok()?),
+        };
+        let directory = PathBuf::from(unescape(fields.next()?));
+        let mut invocations = vec![Invocation::new(unescape(fields.next()?), VecDeque::new())];
+        while let Some(field) = fields.next() {
+            if field == SEPARATOR {
+                invocations.push(Invocation::new(unescape(fields.next()?), VecDeque::new()));
+            } else if let Some(last) = invocations.last_mut() {
+                last.args.push_back(unescape(field));
+            }
+        }
+        Some(Self {
+            started,
+            duration,
+            directory,
+            invocations,
+            code,
+        })
+    }
+}
+
+/// History of runs, stored in a file with one entry per line, oldest first.
+#[derive(Debug, Clone)]
+pub struct History {
+    /// Path to the history file.
+    path: PathBuf,
+}
+
+impl History {
+    /// Creates a history stored at the given path, which is created on first record.
+    #[must_use]
+    pub const fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Gets the path to the history file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the entry, removing old entries if there are too many.
+    ///
+    /// ## Errors
+    ///
+    /// If the history file could not be read or written.
+    pub fn record(&self, entry: &Entry) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", entry.to_line())?;
+        drop(file);
+
+        let content = fs::read_to_string(&self.path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.len() > 2 * MAX_ENTRIES {
+            let kept = lines[lines.len() - MAX_ENTRIES..].join("\n");
+            fs::write(&self.path, kept + "\n")?;
+        }
+        Ok(())
+    }
+
+    /// Reads all entries, oldest first, skipping malformed lines. A missing history file has no entries.
+    ///
+    /// ## Errors
+    ///
+    /// If the history file exists but could not be read.
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        Ok(content.lines().filter_map(Entry::parse).collect())
+    }
+
+    /// Reads entries of runs from the given directory, oldest first, see [`History::entries`].
+    ///
+    /// ## Errors
+    ///
+    /// See [`History::entries`].
+    pub fn entries_in(&self, directory: &Path) -> io::Result<Vec<Entry>> {
+        let mut entries = self.entries()?;
+        entries.retain(|entry| entry.directory == directory);
+        Ok(entries)
+    }
+}
+
+/// Escapes backslashes, tabs and line breaks, so that the field fits on a line.
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', r"\\")
+        .replace('\t', r"\t")
+        .replace('\n', r"\n")
+        .replace('\r', r"\r")
+}
+
+/// Reverses [`escape`].
+fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_entry() {
+        let entry = Entry {
+            started: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            duration: Duration::from_millis(1234),
+            directory: PathBuf::from("/home/user/project"),
+            invocations: vec![
+                Invocation::new(
+                    "test".to_string(),
+                    VecDeque::from(["a b".to_string(), "tab\there".to_string()]),
+                ),
+                Invocation::new("lint".to_string(), VecDeque::from([r"C:\dir".to_string()])),
+            ],
+            code: Some(0),
+        };
+        assert_eq!(Entry::parse(&entry.to_line()), Some(entry.clone()));
+        assert_eq!(
+            entry.command_line(),
+            r"test 'a b' 'tab	here' + lint 'C:\dir'"
+        );
+
+        let replaced = Entry {
+            code: None,
+            invocations: vec![Invocation::new("repl".to_string(), VecDeque::new())],
+            ..entry
+        };
+        assert_eq!(Entry::parse(&replaced.to_line()), Some(replaced));
+        assert_eq!(Entry::parse("garbage"), None);
+    }
+
+    #[test]
+    fn test_history() {
+        let path = env::temp_dir().join(format!("jiu-test-history-{}/history", process::id()));
+        let history = History::new(path.clone());
+        assert_eq!(history.entries().unwrap(), vec![]);
+
+        let entry = |directory: &str, name: &str| Entry {
+            started: SystemTime::UNIX_EPOCH,
+            duration: Duration::ZERO,
+            directory: PathBuf::from(directory),
+            invocations: vec![Invocation::new(name.to_string(), VecDeque::new())],
+            code: Some(0),
+        };
+        history.record(&entry("/a", "build")).unwrap();
+        history.record(&entry("/b", "test")).unwrap();
+        history.record(&entry("/a", "run")).unwrap();
+        assert_eq!(history.entries().unwrap().len(), 3);
+        assert_eq!(
+            history.entries_in(Path::new("/a")).unwrap(),
+            vec![entry("/a", "build"), entry("/a", "run")]
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).expect("Failed to remove directory");
+    }
+}
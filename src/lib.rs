@@ -1,24 +1,69 @@
 //! # `jiu` library crate
 //!
 //! If you are reading this, you are reading the documentation for the `jiu` library crate. For the cli, kindly refer to the README file.
+//!
+//! ## Example
+//!
+//! Recipes can be deserialized from a `.jiu.toml` file into a [`Config`], or constructed programmatically:
+//!
+//! ```
+//! use jiu::{Component, Config, Recipe, Runner};
+//!
+//! let mut recipe = Recipe::new(
+//!     vec!["greet".to_string()],
+//!     vec![Component::literal("echo"), Component::placeholder("+names").unwrap()],
+//! );
+//! recipe.arguments = vec!["+names".parse().unwrap()];
+//!
+//! let config = Config {
+//!     recipes: vec![recipe],
+//!     ..Config::default()
+//! };
+//! let runner = Runner::new(&config);
+//! let resolved = runner.resolve("greet", ["Alice".to_string()].into()).unwrap();
+//! assert_eq!(resolved, vec!["echo", "Alice"]);
+//! ```
 
 #![deny(missing_docs)]
 #![warn(clippy::all, clippy::nursery, clippy::pedantic, clippy::cargo)]
 
-mod arguments;
+pub mod arguments;
 #[cfg(feature = "cli")]
 mod cli;
+mod condition;
+pub mod diagnostic;
+mod duration;
+mod error;
+pub mod executor;
+mod freshness;
+pub mod history;
+mod runner;
+#[cfg(unix)]
+pub mod signals;
+mod suggest;
+mod watch;
 
-use anyhow::{Context, Result, bail};
 use arguments::{ArgumentDefinition, ResolvedArgument};
 #[cfg(feature = "cli")]
-pub use cli::Action;
+pub use cli::{Action, Options};
+pub use condition::Condition;
+use diagnostic::{RecipeSpans, Span};
+pub use error::Error;
+pub use executor::{Executor, Job, Status};
+pub use freshness::{Freshness, UpToDate};
 use owo_colors::OwoColorize;
-use serde::{Deserialize, de::Error};
-use std::collections::{HashMap, VecDeque};
+pub use runner::{Invocation, Notice, RunHooks, Runner};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    process::{Command, Stdio},
+    time::Duration,
+};
+pub use watch::Watcher;
 
 /// The configuration.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Config {
     /// Description of the configuration.
     #[serde(default)]
@@ -29,12 +74,108 @@ pub struct Config {
     /// - Default recipe must be able to accept no arguments.
     #[serde(default)]
     pub default: String,
-    /// Recipes.
+    /// Order of recipes when listing.
     #[serde(default)]
+    pub order: Order,
+    /// Whether to match recipes by unique prefixes of their names.
+    #[serde(default)]
+    pub match_prefix: bool,
+    /// Whether recipes replace the `jiu` process with their command by default, on Unix.
+    ///
+    /// Can be overridden per recipe with [`Recipe::exec`].
+    #[serde(default)]
+    pub exec: bool,
+    /// Whether to print commands to standard error before executing them by default.
+    ///
+    /// Can be overridden per recipe with [`Recipe::echo`].
+    #[serde(default)]
+    pub echo: bool,
+    /// Recipes, each checked with [`Recipe::validate`] when deserialized.
+    #[serde(default, deserialize_with = "deserialize_recipes")]
     pub recipes: Vec<Recipe>,
 }
 
+/// Order of recipes when listing.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// Keep the order in which recipes and groups are declared.
+    #[default]
+    Declaration,
+    /// Sort recipes and groups alphabetically.
+    Alphabetical,
+}
+
 impl Config {
+    /// Deserializes the configuration from TOML, keeping spans of recipe components for diagnostics.
+    ///
+    /// ## Errors
+    ///
+    /// If the source is not a valid configuration, including recipes with a string `command` but no `shell`.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        let mut config: Self = toml::from_str(source)?;
+        let spans = diagnostic::recipe_spans(source)?;
+        for (recipe, spans) in config.recipes.iter_mut().zip(spans) {
+            recipe.spans = spans;
+        }
+        Ok(config)
+    }
+
+    /// Finds the recipe with the given name.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<&Recipe> {
+        self.recipes
+            .iter()
+            .find(|r| r.names.iter().any(|n| n == name))
+    }
+
+    /// Looks up the recipe with the given name, or by a unique prefix of a public recipe's name if [`Config::match_prefix`] is enabled.
+    ///
+    /// ## Errors
+    ///
+    /// - [`Error::RecipeNotFound`]: If no recipe matches the name.
+    /// - [`Error::AmbiguousPrefix`]: If the prefix matches multiple recipes.
+    pub fn lookup(&self, name: &str) -> Result<&Recipe, Error> {
+        if let Some(recipe) = self.find(name) {
+            return Ok(recipe);
+        }
+        let not_found = || Error::RecipeNotFound {
+            name: name.to_string(),
+        };
+        if !self.match_prefix || name.is_empty() {
+            return Err(not_found());
+        }
+
+        let matches: Vec<_> = self
+            .recipes
+            .iter()
+            .filter(|r| !r.is_private() && r.names.iter().any(|n| n.starts_with(name)))
+            .collect();
+        match matches.as_slice() {
+            [] => Err(not_found()),
+            [recipe] => Ok(recipe),
+            _ => Err(Error::AmbiguousPrefix {
+                prefix: name.to_string(),
+                candidates: matches
+                    .iter()
+                    .flat_map(|r| r.names.iter().filter(|n| n.starts_with(name)).cloned())
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Suggests the name of a public recipe that is similar to the given name, useful on typos.
+    #[must_use]
+    pub fn suggest(&self, name: &str) -> Option<&str> {
+        let names = self
+            .recipes
+            .iter()
+            .filter(|r| !r.is_private())
+            .flat_map(|r| r.names.iter().map(String::as_str));
+        suggest::suggest(name, names)
+    }
+
     /// Summarizes the configuration.
     #[must_use]
     pub fn summarize(&self, color: bool) -> String {
@@ -49,50 +190,83 @@ impl Config {
         format!("{description}\n{recipes}")
     }
 
-    /// Summarizes the recipes.
+    /// Summarizes the recipes, rendering grouped recipes under group headings.
     fn summarize_recipes(&self, color: bool) -> String {
-        if self.recipes.is_empty() {
+        // Hide private recipes
+        let public: Vec<_> = self.recipes.iter().filter(|r| !r.is_private()).collect();
+        if public.is_empty() {
             return "No recipes found".to_string();
         }
 
-        // A pack of (definition, definition_length, description)
-        let pack: Vec<_> = self
-            .recipes
-            .iter()
-            .map(|recipe| {
-                let (def, def_len) = recipe.summarize_definition(color);
-                (def, def_len, &recipe.description)
+        // Group recipes, with ungrouped recipes coming first
+        let mut groups: Vec<(&str, Vec<&Recipe>)> = Vec::new();
+        for recipe in public {
+            match groups.iter_mut().find(|(group, _)| *group == recipe.group) {
+                Some((_, recipes)) => recipes.push(recipe),
+                None => groups.push((&recipe.group, vec![recipe])),
+            }
+        }
+        if self.order == Order::Alphabetical {
+            groups.sort_by_key(|(group, _)| *group);
+            for (_, recipes) in &mut groups {
+                recipes.sort_by(|a, b| a.names.cmp(&b.names));
+            }
+        } else if let Some(index) = groups.iter().position(|(group, _)| group.is_empty()) {
+            let ungrouped = groups.remove(index);
+            groups.insert(0, ungrouped);
+        }
+
+        // A pack of (group, definition, definition_length, description)
+        let pack: Vec<_> = groups
+            .into_iter()
+            .flat_map(|(group, recipes)| {
+                recipes.into_iter().map(move |recipe| {
+                    let (def, def_len) = recipe.summarize_definition(color);
+                    // Grouped recipes are indented further
+                    let indent_len = if group.is_empty() { 2 } else { 4 };
+                    (group, def, def_len + indent_len, &recipe.description)
+                })
             })
             .collect();
-        let max_def_len = pack.iter().map(|(_, len, _)| *len).max().unwrap_or(0);
+        let max_def_len = pack.iter().map(|(_, _, len, _)| *len).max().unwrap_or(0);
 
-        let recipes = pack
-            .into_iter()
-            .map(|(def, def_len, description)| {
-                // Calculate required padding
-                let padding = max_def_len.saturating_sub(def_len);
-                let padding = " ".repeat(padding);
-
-                // Format the description
-                let description = if description.is_empty() {
-                    String::new()
+        let mut lines = vec!["Available recipes:".to_string()];
+        let mut current_group = "";
+        for (group, def, def_len, description) in pack {
+            // Add a heading when entering a new group
+            if group != current_group {
+                let heading = format!("[{group}]");
+                let heading = if color {
+                    heading.bold().to_string()
                 } else {
-                    let s = format!(" # {description}");
-                    if color { s.dimmed().to_string() } else { s }
+                    heading
                 };
+                lines.push(format!("  {heading}"));
+                current_group = group;
+            }
+            // Calculate required indentation and padding
+            let indent = if group.is_empty() { "  " } else { "    " };
+            let padding = max_def_len.saturating_sub(def_len);
+            let padding = " ".repeat(padding);
 
-                // Format the summary for this recipe
-                format!("  {def}{padding}{description}")
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+            // Format the description
+            let description = if description.is_empty() {
+                String::new()
+            } else {
+                let s = format!(" # {description}");
+                if color { s.dimmed().to_string() } else { s }
+            };
 
-        format!("Available recipes:\n{recipes}")
+            // Format the summary for this recipe
+            lines.push(format!("{indent}{def}{padding}{description}"));
+        }
+
+        lines.join("\n")
     }
 }
 
 /// The recipe.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Recipe {
     /// Names of the recipe.
     ///
@@ -101,83 +275,340 @@ pub struct Recipe {
     /// Description of the recipe.
     #[serde(default)]
     pub description: String,
+    /// Group of the recipe, used to organize recipes when listing.
+    ///
+    /// Recipes without a group are listed first.
+    #[serde(default)]
+    pub group: String,
+    /// Whether the recipe is private.
+    ///
+    /// Private recipes are hidden when listing, but can still be invoked explicitly. Recipes whose first name starts with `_` are also private.
+    #[serde(default)]
+    pub private: bool,
+    /// Whether to replace the `jiu` process with the command on Unix, instead of spawning a child process.
+    ///
+    /// Defaults to [`Config::exec`] if not set. Only takes effect for the last recipe to run in sequence.
+    #[serde(default)]
+    pub exec: Option<bool>,
+    /// Whether to print the command to standard error before executing it.
+    ///
+    /// Defaults to [`Config::echo`] if not set.
+    #[serde(default)]
+    pub echo: Option<bool>,
+    /// Time after which the command is terminated, e.g. `10m` or `1h30m`.
+    ///
+    /// The command is asked to terminate first, and killed if it does not exit within a grace period.
+    #[serde(default, deserialize_with = "duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+    /// Number of times to rerun the command after it fails.
+    #[serde(default)]
+    pub retries: u32,
+    /// Time to wait before rerunning a failed command, e.g. `5s`.
+    #[serde(default, deserialize_with = "duration::deserialize_option")]
+    pub retry_delay: Option<Duration>,
+    /// Exit codes treated as success, or only `0` if empty.
+    #[serde(default)]
+    pub success_codes: Vec<i32>,
+    /// Name to capture the trimmed standard output of the command as, for later recipes to reference as `["@name"]`.
+    #[serde(default)]
+    pub capture: Option<String>,
+    /// Conditions that must be met for the recipe to run, or it is skipped.
+    #[serde(default)]
+    pub when: Condition,
+    /// Glob patterns of files the outputs of the recipe are generated from.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Glob patterns of files generated by the recipe, which is skipped if they are up to date with its sources.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// How to check whether the outputs are up to date with the sources.
+    #[serde(default)]
+    pub up_to_date: UpToDate,
+    /// Glob patterns of files to watch for changes with `--watch`, or all files not ignored by `.gitignore` if empty.
+    #[serde(default)]
+    pub watch: Vec<String>,
     /// Arguments to the recipe.
     #[serde(default)]
-    arguments: Vec<ArgumentDefinition>,
-    /// Command to run.
-    command: Vec<Component>,
+    pub arguments: Vec<ArgumentDefinition>,
+    /// Inline script to run instead of the command, written to a temporary file with arguments passed in definition order.
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Interpreter to run the inline script with, e.g. `python3`, or the script is executed directly relying on its shebang line.
+    #[serde(default)]
+    pub interpreter: Option<String>,
+    /// Shell to run the command with as a script, e.g. `sh` or `bash`.
+    ///
+    /// Arguments are passed as positional parameters in definition order, instead of being interpolated into the script.
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Command to run, or lines of the script in shell mode, selected for the current operating system if given as a table.
+    #[serde(default, deserialize_with = "deserialize_command")]
+    pub command: Vec<Component>,
+    /// Spans of the recipe in the config file, used for diagnostics.
+    #[serde(skip)]
+    pub spans: RecipeSpans,
 }
 
 impl Recipe {
+    /// Creates a new recipe with the given names and command, leaving other fields as default.
+    #[must_use]
+    pub fn new(names: Vec<String>, command: Vec<Component>) -> Self {
+        Self {
+            names,
+            command,
+            ..Self::default()
+        }
+    }
+
+    /// Checks that the recipe has something to run, and that its fields can be used together.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::InvalidRecipe`]: If the recipe has neither a command nor a script, combines a script with a command or shell, sets an interpreter without a script, or sets sources without outputs to compare their modification times with.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: &str| {
+            Err(Error::InvalidRecipe {
+                name: self.names.first().cloned().unwrap_or_default(),
+                message: message.to_string(),
+            })
+        };
+        match (&self.script, self.command.is_empty()) {
+            (None, true) => invalid("either a non-empty `command` or a `script` is required"),
+            (Some(_), false) => invalid("`script` cannot be combined with `command`"),
+            (Some(_), true) if self.shell.is_some() => {
+                invalid("`script` cannot be combined with `shell`")
+            }
+            (None, false) if self.interpreter.is_some() => {
+                invalid("`interpreter` can only be used with `script`")
+            }
+            _ if self.up_to_date == UpToDate::Mtime
+                && self.outputs.is_empty()
+                && !self.sources.is_empty() =>
+            {
+                invalid("`sources` require `outputs` when `up_to_date` is \"mtime\"")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether the recipe is private, i.e. hidden when listing.
+    #[must_use]
+    pub fn is_private(&self) -> bool {
+        self.private || self.names.first().is_some_and(|name| name.starts_with('_'))
+    }
+
     /// Resolves to a command with the given arguments.
     ///
+    /// For recipes with an inline [`Recipe::script`], resolves to the arguments passed to the script instead.
+    ///
     /// ## Errors
     ///
-    /// - If an argument could not be resolved.
-    /// - If a referenced argument is not defined.
-    /// - If a referenced argument does not match the defined type.
-    /// - If unexpected arguments are left after resolving.
-    pub fn resolve(self, mut args: VecDeque<String>) -> Result<Vec<String>> {
-        let Self {
-            arguments, command, ..
-        } = self;
+    /// - [`Error::MissingArgument`]: If an argument could not be resolved.
+    /// - [`Error::UndefinedPlaceholder`]: If a referenced argument is not defined.
+    /// - [`Error::TypeMismatch`]: If a referenced argument does not match the defined type.
+    /// - [`Error::EnvVarMissing`]: If a referenced environment variable is not set.
+    /// - [`Error::UnexpectedArguments`]: If unexpected arguments are left after resolving.
+    /// - [`Error::CaptureMissing`]: If a captured value is referenced, see [`Recipe::resolve_with`].
+    /// - [`Error::SubstitutionFailed`]: If a command substitution could not be executed or failed.
+    pub fn resolve(&self, args: VecDeque<String>) -> Result<Vec<String>, Error> {
+        self.resolve_with(args, &HashMap::new())
+    }
+
+    /// Resolves to a command with the given arguments and values captured from earlier recipes.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Recipe::resolve`], where [`Error::CaptureMissing`] is returned if a referenced value has not been captured.
+    pub fn resolve_with(
+        &self,
+        args: VecDeque<String>,
+        captured: &HashMap<String, String>,
+    ) -> Result<Vec<String>, Error> {
+        self.resolve_substituting(args, captured, true)
+    }
 
+    /// Checks that the recipe resolves with the given arguments and values captured so far like [`Recipe::resolve_with`], without executing command substitutions.
+    ///
+    /// Useful to check recipes before running any of them, resolving each again right before it runs.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Recipe::resolve_with`], apart from [`Error::SubstitutionFailed`].
+    pub fn check_with(
+        &self,
+        args: VecDeque<String>,
+        captured: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        self.resolve_substituting(args, captured, false).map(drop)
+    }
+
+    /// Resolves to a command like [`Recipe::resolve_with`], executing command substitutions only if `substitute` is set, or leaving them empty otherwise.
+    fn resolve_substituting(
+        &self,
+        mut args: VecDeque<String>,
+        captured: &HashMap<String, String>,
+        substitute: bool,
+    ) -> Result<Vec<String>, Error> {
         // Resolve the arguments
         let mut resolved_args = HashMap::new();
-        for arg in arguments {
-            let resolved_arg = arg.arg_type.resolve(&mut args).with_context(|| {
-                format!("While resolving argument \"{}\"", arg.summarize(false).0)
-            })?;
-            resolved_args.insert(arg.name, resolved_arg);
+        for arg in &self.arguments {
+            let resolved_arg = arg.resolve(&mut args)?;
+            resolved_args.insert(arg.name.as_str(), resolved_arg);
         }
 
-        // Resolve the command
+        // Resolve the command, passing arguments as positional parameters to scripts
+        let resolved_command = match (&self.script, &self.shell) {
+            (Some(_), _) => self.positional_arguments(&resolved_args),
+            (None, Some(shell)) => self.resolve_shell(shell, &resolved_args)?,
+            (None, None) => self.resolve_command(&resolved_args, captured, substitute)?,
+        };
+
+        // Check if there are any remaining arguments
+        if !args.is_empty() {
+            return Err(Error::UnexpectedArguments(args.into()));
+        }
+
+        Ok(resolved_command)
+    }
+
+    /// Resolves the components of the command with the resolved arguments, executing command substitutions if `substitute` is set.
+    fn resolve_command(
+        &self,
+        resolved_args: &HashMap<&str, ResolvedArgument>,
+        captured: &HashMap<String, String>,
+        substitute: bool,
+    ) -> Result<Vec<String>, Error> {
         let mut resolved_command = Vec::new();
-        for component in command {
+        for component in &self.command {
             match component {
-                Component::Literal(literal) => resolved_command.push(literal),
+                Component::Literal(literal) => resolved_command.push(literal.clone()),
                 Component::Argument(ref_arg) => {
-                    let Some(resolved_arg) = resolved_args.get(&ref_arg.name) else {
-                        bail!("Argument {} not found", ref_arg.name);
+                    let Some(resolved_arg) = resolved_args.get(ref_arg.name.as_str()) else {
+                        return Err(Error::UndefinedPlaceholder {
+                            name: ref_arg.name.clone(),
+                        });
                     };
                     if !resolved_arg.matches(&ref_arg.arg_type) {
-                        bail!(
-                            "Argument \"{}\" defined as {} but referenced as {}",
-                            ref_arg.name,
-                            resolved_arg.arg_type(),
-                            ref_arg.arg_type,
-                        );
-                    }
-                    match resolved_arg {
-                        ResolvedArgument::Required(value) => resolved_command.push(value.clone()),
-                        ResolvedArgument::Optional(value) => {
-                            if let Some(v) = value {
-                                resolved_command.push(v.clone());
-                            }
-                        }
-                        ResolvedArgument::Variadic(values)
-                        | ResolvedArgument::RequiredVariadic(values) => {
-                            for value in values {
-                                resolved_command.push(value.clone());
-                            }
-                        }
+                        return Err(Error::TypeMismatch {
+                            name: ref_arg.name.clone(),
+                            defined: resolved_arg.arg_type(),
+                            referenced: ref_arg.arg_type.clone(),
+                        });
                     }
+                    resolved_command.extend_from_slice(resolved_arg.values());
                 }
                 Component::EnvVar(var_name) => {
-                    let value = std::env::var(&var_name)?;
+                    let value = std::env::var(var_name).map_err(|source| Error::EnvVarMissing {
+                        name: var_name.clone(),
+                        source,
+                    })?;
                     resolved_command.push(value);
                 }
+                Component::Captured(name) => {
+                    let value = captured
+                        .get(name)
+                        .ok_or_else(|| Error::CaptureMissing { name: name.clone() })?;
+                    resolved_command.push(value.clone());
+                }
+                Component::Substitution(command) if substitute => {
+                    resolved_command.push(execute_substitution(command)?);
+                }
+                Component::Substitution(_) => resolved_command.push(String::new()),
             }
         }
+        Ok(resolved_command)
+    }
 
-        // Check if there are any remaining arguments
-        if !args.is_empty() {
-            bail!("Unexpected argument(s): {args:?}");
+    /// Resolves the script to run with the shell, passing the resolved arguments as positional parameters.
+    fn resolve_shell(
+        &self,
+        shell: &str,
+        resolved_args: &HashMap<&str, ResolvedArgument>,
+    ) -> Result<Vec<String>, Error> {
+        // Placeholders are never interpolated into the script, since values would be split and expanded by the shell
+        let mut lines = Vec::with_capacity(self.command.len());
+        for component in &self.command {
+            let Component::Literal(line) = component else {
+                return Err(Error::ScriptPlaceholder {
+                    placeholder: component.to_string(),
+                });
+            };
+            lines.push(line.as_str());
         }
 
+        // The first name is passed as `$0`, so that the arguments start from `$1`
+        let mut resolved_command = vec![
+            shell.to_string(),
+            "-c".to_string(),
+            lines.join("\n"),
+            self.names.first().cloned().unwrap_or_default(),
+        ];
+        resolved_command.extend(self.positional_arguments(resolved_args));
         Ok(resolved_command)
     }
 
+    /// Collects values of the resolved arguments in definition order.
+    fn positional_arguments(&self, resolved_args: &HashMap<&str, ResolvedArgument>) -> Vec<String> {
+        self.arguments
+            .iter()
+            .filter_map(|arg| resolved_args.get(arg.name.as_str()))
+            .flat_map(|resolved_arg| resolved_arg.values().iter().cloned())
+            .collect()
+    }
+
+    /// Locates the part of the recipe in the config file that caused the error, if known.
+    #[must_use]
+    pub fn locate(&self, err: &Error) -> Option<Span> {
+        match err {
+            Error::MissingArgument { name, .. } => {
+                let index = self.arguments.iter().position(|arg| arg.name == *name)?;
+                self.spans.arguments.get(index).cloned()
+            }
+            Error::UndefinedPlaceholder { name } => {
+                let index = self.command.iter().position(
+                    |component| matches!(component, Component::Argument(arg) if arg.name == *name),
+                )?;
+                self.spans.command.get(index).cloned()
+            }
+            Error::TypeMismatch {
+                name, referenced, ..
+            } => {
+                let index = self.command.iter().position(|component| {
+                    matches!(component, Component::Argument(arg) if arg.name == *name && arg.arg_type == *referenced)
+                })?;
+                self.spans.command.get(index).cloned()
+            }
+            Error::CaptureMissing { name } => {
+                let index = self.command.iter().position(
+                    |component| matches!(component, Component::Captured(captured) if captured == name),
+                )?;
+                self.spans.command.get(index).cloned()
+            }
+            Error::ScriptPlaceholder { placeholder } => {
+                let index = self
+                    .command
+                    .iter()
+                    .position(|component| component.to_string() == *placeholder)?;
+                self.spans.command.get(index).cloned()
+            }
+            Error::SubstitutionFailed { command, .. } => {
+                let index = self.command.iter().position(
+                    |component| matches!(component, Component::Substitution(substituted) if substituted == command),
+                )?;
+                self.spans.command.get(index).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks whether the command references values captured from earlier recipes.
+    #[must_use]
+    pub fn references_captured(&self) -> bool {
+        self.command
+            .iter()
+            .any(|component| matches!(component, Component::Captured(_)))
+    }
+
     /// Summarizes the recipe definition, returning a string representation and the length.
     #[must_use]
     pub fn summarize_definition(&self, color: bool) -> (String, usize) {
@@ -210,13 +641,65 @@ impl Recipe {
 
 /// A component of a command.
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum Component {
+pub enum Component {
     /// A string literal.
     Literal(String),
     /// An argument.
     Argument(ArgumentDefinition),
     /// An environment variable.
     EnvVar(String),
+    /// A value captured from the output of an earlier recipe.
+    Captured(String),
+    /// The trimmed standard output of a command, program first, executed when resolving.
+    Substitution(Vec<String>),
+}
+
+impl Component {
+    /// Creates a string literal component.
+    #[must_use]
+    pub fn literal(literal: impl Into<String>) -> Self {
+        Self::Literal(literal.into())
+    }
+
+    /// Creates a placeholder component from its representation in the config file, e.g. `?arg`, `$VAR`, `@captured` or `!program`.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::EmptyArgument`]: If the placeholder is empty.
+    pub fn placeholder(placeholder: &str) -> Result<Self, Error> {
+        // Parse the content as an environment variable (if starts with $)
+        if let Some(var_name) = placeholder.strip_prefix('$') {
+            return Ok(Self::EnvVar(var_name.to_string()));
+        }
+
+        // Parse the content as a command substitution (if starts with !)
+        if let Some(program) = placeholder.strip_prefix('!') {
+            if program.is_empty() {
+                return Err(Error::EmptyArgument);
+            }
+            return Ok(Self::Substitution(vec![program.to_string()]));
+        }
+
+        // Parse the content as a captured value (if starts with @)
+        if let Some(name) = placeholder.strip_prefix('@') {
+            return Ok(Self::Captured(name.to_string()));
+        }
+
+        // Parse the content as an argument
+        Ok(Self::Argument(placeholder.parse()?))
+    }
+}
+
+impl Display for Component {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Literal(literal) => write!(f, "{literal}"),
+            Self::Argument(arg) => write!(f, "[{}]", arg.summarize(false).0),
+            Self::EnvVar(var_name) => write!(f, "[${var_name}]"),
+            Self::Captured(name) => write!(f, "[@{name}]"),
+            Self::Substitution(command) => write!(f, "[!{}]", command.join(" ")),
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Component {
@@ -224,6 +707,8 @@ impl<'de> Deserialize<'de> for Component {
     where
         D: serde::Deserializer<'de>,
     {
+        use serde::de::Error;
+
         #[derive(Deserialize)]
         #[serde(untagged)]
         enum InnerRepr {
@@ -232,33 +717,157 @@ impl<'de> Deserialize<'de> for Component {
         }
 
         match InnerRepr::deserialize(deserializer)? {
-            InnerRepr::Array(mut array) => {
-                // Only accept arrays of length 1
+            InnerRepr::Array(array) => {
+                let mut array = array.into_iter();
                 let placeholder = array
-                    .pop()
+                    .next()
                     .ok_or_else(|| Error::custom("Expected a single argument, but got none"))?;
-                if !array.is_empty() {
-                    return Err(Error::custom(
-                        "Expected a single argument, but got multiple",
-                    ));
-                }
 
-                // Parse the content as an environment variable (if starts with $)
-                if placeholder.starts_with('$') {
-                    let mut var_name = placeholder;
-                    var_name.remove(0); // Remove the leading $
-                    return Ok(Self::EnvVar(var_name));
+                // Only accept arrays of length 1, except for command substitutions taking arguments
+                match Self::placeholder(&placeholder).map_err(Error::custom)? {
+                    Self::Substitution(mut command) => {
+                        command.extend(array);
+                        Ok(Self::Substitution(command))
+                    }
+                    _ if array.len() > 0 => Err(Error::custom(
+                        "Expected a single argument, but got multiple",
+                    )),
+                    component => Ok(component),
                 }
-
-                // Parse the content as an argument
-                let arg = ArgumentDefinition::from_string::<D>(placeholder)?;
-                Ok(Self::Argument(arg))
             }
             InnerRepr::Literal(literal) => Ok(Self::Literal(literal)),
         }
     }
 }
 
+/// Deserializes the recipes, rejecting invalid ones with [`Recipe::validate`].
+fn deserialize_recipes<'de, D>(deserializer: D) -> Result<Vec<Recipe>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    /// A recipe validated as soon as it is deserialized, so that errors point at it.
+    struct Validated(Recipe);
+
+    impl<'de> Deserialize<'de> for Validated {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            // Deserializing as a newtype lets the format locate errors raised after the fields are read
+            deserializer.deserialize_newtype_struct("Validated", ValidatedVisitor)
+        }
+    }
+
+    /// Visitor validating the recipe read from the inner deserializer.
+    struct ValidatedVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for ValidatedVisitor {
+        type Value = Validated;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a recipe")
+        }
+
+        fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let recipe = Recipe::deserialize(deserializer)?;
+            recipe.validate().map_err(serde::de::Error::custom)?;
+            Ok(Validated(recipe))
+        }
+    }
+
+    let recipes = Vec::<Validated>::deserialize(deserializer)?;
+    Ok(recipes
+        .into_iter()
+        .map(|Validated(recipe)| recipe)
+        .collect())
+}
+
+/// Deserializes the command from a list of components, or a single string as the script in shell mode, or a table of either keyed by operating system.
+fn deserialize_command<'de, D>(deserializer: D) -> Result<Vec<Component>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct CommandVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for CommandVisitor {
+        type Value = Vec<Component>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(
+                f,
+                "a list of components, a script string or a table of them"
+            )
+        }
+
+        fn visit_str<E: serde::de::Error>(self, script: &str) -> Result<Self::Value, E> {
+            Ok(vec![Component::literal(script)])
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            /// The command for an operating system.
+            #[derive(Deserialize)]
+            struct OsCommand(#[serde(deserialize_with = "deserialize_command")] Vec<Component>);
+
+            let table: HashMap<String, OsCommand> =
+                HashMap::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+            let mut keys: Vec<String> = table.keys().cloned().collect();
+            keys.sort();
+            condition::select_os(table)
+                .map(|OsCommand(command)| command)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!(
+                        "No command for operating system \"{}\" or family \"{}\", and no \"default\", only for {keys:?}",
+                        std::env::consts::OS,
+                        std::env::consts::FAMILY
+                    ))
+                })
+        }
+    }
+
+    deserializer.deserialize_any(CommandVisitor)
+}
+
+/// Executes the command of a substitution, returning its trimmed standard output.
+fn execute_substitution(command: &[String]) -> Result<String, Error> {
+    let failed = |message: String| Error::SubstitutionFailed {
+        command: command.to_vec(),
+        message,
+    };
+    let Some((program, args)) = command.split_first() else {
+        return Err(failed("Empty command".to_string()));
+    };
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| failed(err.to_string()))?;
+    #[cfg(unix)]
+    let _guard = signals::ChildGuard::new(child.id());
+    let output = child
+        .wait_with_output()
+        .map_err(|err| failed(err.to_string()))?;
+    if !output.status.success() {
+        let status = Status::from(output.status);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stderr = stderr.trim();
+        return Err(failed(if stderr.is_empty() {
+            status.to_string()
+        } else {
+            format!("{status}: {stderr}")
+        }));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -316,4 +925,201 @@ mod tests {
             Component::Argument(recipe.arguments[3].clone())
         );
     }
+
+    #[test]
+    fn test_summarize_groups() {
+        let config: Config = toml::from_str(
+            r#"
+            order = "alphabetical"
+            [[recipes]]
+            names = ["lint"]
+            group = "check"
+            command = ["cargo", "clippy"]
+            [[recipes]]
+            names = ["build", "b"]
+            description = "Build"
+            command = ["cargo", "build"]
+            [[recipes]]
+            names = ["_setup"]
+            command = ["./setup.sh"]
+            [[recipes]]
+            names = ["hidden"]
+            group = "check"
+            private = true
+            command = ["true"]
+            [[recipes]]
+            names = ["fmt"]
+            group = "check"
+            description = "Format"
+            command = ["cargo", "fmt"]
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.summarize_recipes(false),
+            "Available recipes:\n  build/b # Build\n  [check]\n    fmt   # Format\n    lint "
+        );
+    }
+
+    #[test]
+    fn test_substitution() {
+        let config: Config = toml::from_str(
+            r#"
+            [[recipes]]
+            names = ["build"]
+            arguments = ["?tag"]
+            command = ["docker", "build", "--build-arg", ["!git", "rev-parse", "HEAD"], ["!date"], ["?tag"], ["$HOME"], ["@version"]]
+        "#,
+        )
+        .unwrap();
+        let recipe = &config.recipes[0];
+        assert_eq!(
+            recipe.command[3],
+            Component::Substitution(vec![
+                "git".to_string(),
+                "rev-parse".to_string(),
+                "HEAD".to_string()
+            ])
+        );
+        assert_eq!(
+            recipe.command[4],
+            Component::Substitution(vec!["date".to_string()])
+        );
+
+        let command: Vec<String> = recipe.command.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            command.join(" "),
+            "docker build --build-arg [!git rev-parse HEAD] [!date] [?tag] [$HOME] [@version]"
+        );
+
+        assert!(
+            toml::from_str::<Config>(
+                r#"
+                [[recipes]]
+                names = ["multiple"]
+                command = [["arg", "extra"]]
+            "#
+            )
+            .is_err()
+        );
+        assert_eq!(Component::placeholder("!"), Err(Error::EmptyArgument));
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = [
+            ("", "either a non-empty `command` or a `script` is required"),
+            (
+                "command = []",
+                "either a non-empty `command` or a `script` is required",
+            ),
+            (
+                "script = \"echo\"\ncommand = [\"echo\"]",
+                "`script` cannot be combined with `command`",
+            ),
+            (
+                "script = \"echo\"\nshell = \"sh\"",
+                "`script` cannot be combined with `shell`",
+            ),
+            (
+                "interpreter = \"sh\"\ncommand = [\"echo\"]",
+                "`interpreter` can only be used with `script`",
+            ),
+            (
+                "sources = [\"*.in\"]\ncommand = [\"echo\"]",
+                "`sources` require `outputs` when `up_to_date` is \"mtime\"",
+            ),
+        ];
+        for (fields, message) in invalid {
+            let source = format!(
+                "[[recipes]]\nnames = [\"valid\"]\ncommand = [\"true\"]\n\n[[recipes]]\nnames = [\"invalid\"]\n{fields}\n"
+            );
+            let err = toml::from_str::<Config>(&source).unwrap_err();
+            assert_eq!(
+                err.message(),
+                format!("Recipe \"invalid\" is invalid: {message}")
+            );
+            // Located at the invalid recipe rather than the first one
+            assert!(source[err.span().unwrap()].starts_with("[[recipes]]\nnames = [\"invalid\"]"));
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [[recipes]]
+            names = ["script"]
+            interpreter = "sh"
+            script = "echo"
+
+            [[recipes]]
+            names = ["hash"]
+            sources = ["*.in"]
+            up_to_date = "hash"
+            command = ["echo"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.recipes[0].validate(), Ok(()));
+        assert_eq!(config.recipes[1].validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_shell() {
+        let config: Config = toml::from_str(
+            r#"
+            [[recipes]]
+            names = ["count"]
+            shell = "sh"
+            arguments = ["pattern", "?dir", "*rest"]
+            command = "grep -r \"$1\" | wc -l"
+
+            [[recipes]]
+            names = ["interpolate"]
+            shell = "bash"
+            arguments = ["name"]
+            command = ["echo hello", ["name"]]
+        "#,
+        )
+        .unwrap();
+
+        let resolved = config.recipes[0]
+            .resolve(VecDeque::from(vec!["a b; rm -rf /".to_string()]))
+            .unwrap();
+        assert_eq!(
+            resolved,
+            vec![
+                "sh",
+                "-c",
+                "grep -r \"$1\" | wc -l",
+                "count",
+                "a b; rm -rf /"
+            ]
+        );
+
+        let err = config.recipes[1]
+            .resolve(VecDeque::from(vec!["world".to_string()]))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::ScriptPlaceholder {
+                placeholder: "[name]".to_string()
+            }
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_script_without_shell() {
+        for command in ["\"grep -r TODO | wc -l\"", "{ default = \"make\" }"] {
+            let source = format!(
+                "[[recipes]]\nnames = [\"valid\"]\nshell = \"sh\"\ncommand = \"true\"\n\n[[recipes]]\nnames = [\"invalid\"]\ncommand = {command}\n"
+            );
+            let err = Config::from_toml(&source).unwrap_err();
+            assert_eq!(
+                err.message(),
+                "Recipe \"invalid\" is invalid: a string `command` requires `shell`"
+            );
+            assert!(source[err.span().unwrap()].starts_with("[[recipes]]\nnames = [\"invalid\"]"));
+        }
+    }
 }
@@ -1,15 +1,27 @@
 #![warn(clippy::all, clippy::nursery, clippy::pedantic, clippy::cargo)]
 
-use anyhow::{Context, Ok, Result, bail};
-use jiu::{Action, Config};
-use std::{collections::VecDeque, env, fs};
+use anyhow::{Context, Ok, Result, anyhow, bail};
+use jiu::{
+    Action, Config, Error, Invocation, Job, Notice, Options, RunHooks, Runner, Status, Watcher,
+    diagnostic,
+    executor::Cancel,
+    history::{Entry, History},
+};
+use owo_colors::OwoColorize;
+use std::{
+    cell::Cell,
+    collections::{HashMap, VecDeque},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError, mpsc},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 use supports_color::Stream;
 
 fn main() -> Result<()> {
     // Checking environment
-    let color = supports_color::on(Stream::Stdout)
-        .map(|level| level.has_basic)
-        .unwrap_or(false);
+    let color = supports_color::on(Stream::Stdout).is_some_and(|level| level.has_basic);
     let debug = env::var("JIU_DEBUG").is_ok();
 
     // Collecting arguments
@@ -18,70 +30,706 @@ fn main() -> Result<()> {
     let mut args: VecDeque<String> = iter.collect();
 
     // Resolving actions
-    let action = Action::parse(&mut args)?;
-    let (config, recipe_name) = match action {
+    let (action, options) = Action::parse(&mut args)?;
+    let Some((config, file, invocations)) =
+        resolve_action(action, args, &program_name, color, debug)?
+    else {
+        return Ok(());
+    };
+
+    // Forwarding signals to child processes
+    #[cfg(unix)]
+    jiu::signals::forward_signals().context("Error registering signal handlers")?;
+
+    // Recording history is best effort, so that recipes still run without a data directory
+    let history = (!options.watch).then(history).and_then(|history| {
+        history
+            .inspect_err(|err| eprintln!("Warning: {err:#}, so the run is not recorded"))
+            .ok()
+    });
+    let recorder = Recorder::new(history, file.directory(), &invocations);
+    let on_notice = |notice: &Notice| report(notice, color);
+    let runner = Runner::new(&config)
+        .on_notice(&on_notice)
+        .force(options.force)
+        .echo(options.echo);
+    let mut session = Session {
+        runner,
+        file: &file,
+        options: &options,
+        recorder,
+        timings: Timings::new(options.time || env::var_os("JIU_TIMINGS").is_some()),
+        cancel: None,
+        color,
+        debug,
+    };
+
+    // Checking the recipes before running any of them
+    for Invocation { name, args } in &invocations {
+        if debug {
+            eprintln!("I am \"{program_name}\" running recipe \"{name}\"");
+            eprintln!("Received recipe arguments: {args:?}");
+        }
+        check_recipe(&config, name, color)?;
+        if debug {
+            let recipe = config.lookup(name)?;
+            let command: Vec<String> = recipe.command.iter().map(ToString::to_string).collect();
+            eprintln!("Recipe command: {}", command.join(" "));
+        }
+        session.timings.add(name.clone());
+    }
+    session.check(&invocations)?;
+
+    // Rerunning the recipes on changes to watched files
+    if options.watch {
+        return session.watch(&invocations);
+    }
+
+    // Runs failing with an error exit with code 1
+    let status = session
+        .execute(&invocations)
+        .inspect_err(|_| session.recorder.record(Some(1)))?;
+    session.timings.summarize();
+    session.recorder.record(Some(status.code()));
+    std::process::exit(status.code());
+}
+
+/// Performs actions not running recipes, or locates the config file and collects the invocations to run.
+fn resolve_action(
+    action: Action,
+    args: VecDeque<String>,
+    program_name: &str,
+    color: bool,
+    debug: bool,
+) -> Result<Option<(Config, ConfigFile, Vec<Invocation>)>> {
+    match action {
         Action::Help => {
-            help(&program_name);
-            return Ok(());
+            help(program_name);
+            Ok(None)
         }
         Action::Version => {
             version();
-            return Ok(());
+            Ok(None)
         }
         Action::List => {
-            let config = locate_config_file(debug)?;
+            let (config, _) = locate_config_file(debug)?;
             println!("{}", config.summarize(color));
-            return Ok(());
+            Ok(None)
+        }
+        Action::History => {
+            let (_, file) = locate_config_file(debug)?;
+            show_history(&history()?, file.directory())?;
+            Ok(None)
+        }
+        Action::Last => {
+            let (config, file) = locate_config_file(debug)?;
+            let Some(last) = history()?.entries_in(file.directory())?.pop() else {
+                bail!(
+                    "No recipes have been run in \"{}\"",
+                    file.directory().display()
+                );
+            };
+            eprintln!("Rerunning: {}", last.command_line());
+            Ok(Some((config, file, last.invocations)))
         }
         Action::Default => {
-            let config = locate_config_file(debug)?;
+            let (config, file) = locate_config_file(debug)?;
             if config.default.is_empty() {
                 println!("{}", config.summarize(color));
-                return Ok(());
+                return Ok(None);
             }
-            let default = config.default.clone();
-            (config, default)
+            let default = Invocation::new(config.default.clone(), args);
+            Ok(Some((config, file, vec![default])))
         }
-        Action::Recipe(name) => {
-            let config = locate_config_file(debug)?;
-            (config, name)
+        Action::Recipes(invocations) => {
+            let (config, file) = locate_config_file(debug)?;
+            Ok(Some((config, file, invocations)))
         }
-    };
+    }
+}
 
-    if debug {
-        eprintln!("I am \"{program_name}\" running recipe \"{recipe_name}\"");
-        eprintln!("Received recipe arguments: {args:?}");
+/// Recipes being run, with everything needed to run them again when watching files.
+struct Session<'a> {
+    /// Runner of the recipes.
+    runner: Runner<'a>,
+    /// Config file of the recipes, for diagnostics.
+    file: &'a ConfigFile,
+    /// Options from the command line.
+    options: &'a Options,
+    /// Recorder of the run to the history.
+    recorder: Recorder,
+    /// Report of the durations of the recipes.
+    timings: Timings,
+    /// Flag to cancel running commands with, when watching files.
+    cancel: Option<Cancel>,
+    /// Whether to color output.
+    color: bool,
+    /// Whether to print debug information.
+    debug: bool,
+}
+
+impl Session<'_> {
+    /// Checks that the recipes resolve before running any of them, with values captured by earlier recipes not known yet.
+    fn check(&self, invocations: &[Invocation]) -> Result<()> {
+        if self.options.jobs.is_none() {
+            return self
+                .runner
+                .check_all(invocations, &Sequence::new(self, invocations));
+        }
+        // Captured values are only passed along when running in sequence
+        let config = self.runner.config();
+        for Invocation { name, args } in invocations {
+            // Conditions are evaluated right before running, so recipes not meeting them yet are only checked then
+            if config.lookup(name)?.when.is_met() {
+                self.runner
+                    .check_with(name, args.clone(), &HashMap::new())
+                    .map_err(|err| self.file.diagnose(config, name, err))?;
+            }
+        }
+        Ok(())
     }
 
-    // Finding the recipe
-    let Some(recipe) = config
-        .recipes
-        .into_iter()
-        .find(|r| r.names.contains(&recipe_name))
-    else {
-        bail!("Recipe \"{recipe_name}\" not found");
-    };
+    /// Resolves the invocation to a job right before executing it.
+    fn prepare(&self, Invocation { name, args }: &Invocation) -> Result<Job> {
+        let job = self
+            .runner
+            .prepare(name, args.clone())
+            .map_err(|err| self.file.diagnose(self.runner.config(), name, err))?;
+        Ok(self.attach(job))
+    }
+
+    /// Attaches the flag to cancel the job with when watching files.
+    fn attach(&self, job: Job) -> Job {
+        if self.debug {
+            eprintln!("Resolved command: {:?}", job.command);
+        }
+        Job {
+            cancel: self.cancel.clone(),
+            ..job
+        }
+    }
+
+    /// Executes the recipes concurrently if requested, or in sequence otherwise, returning the status to exit with.
+    fn execute(&self, invocations: &[Invocation]) -> Result<Status> {
+        // Executing the commands concurrently
+        if let Some(jobs) = self.options.jobs {
+            // Conditions are evaluated for all recipes right before launching them, as they run at once
+            let mut commands = Vec::with_capacity(invocations.len());
+            let mut indices = Vec::with_capacity(invocations.len());
+            for (i, invocation) in invocations.iter().enumerate() {
+                if self.runner.skip(&invocation.name)? {
+                    self.timings.skip(i);
+                    continue;
+                }
+                let job = self.prepare(invocation)?;
+                commands.push((invocation.name.clone(), job));
+                indices.push(i);
+            }
+            let status = self.runner.execute_parallel_with(
+                &commands,
+                jobs,
+                self.color,
+                |i, status, duration| {
+                    self.timings.finish(indices[i], status, duration);
+                },
+            )?;
+            if self.debug {
+                eprintln!("Commands exited with {status}");
+            }
+            return Ok(status);
+        }
+
+        // Executing the commands in sequence
+        self.run_sequence(invocations)
+    }
+
+    /// Executes the recipes whenever watched files change, cancelling running commands first.
+    fn watch(mut self, invocations: &[Invocation]) -> Result<()> {
+        // Recipes without patterns watch all files, so any of them falls back to walking the directory
+        let config = self.runner.config();
+        let mut patterns = Vec::new();
+        for Invocation { name, .. } in invocations {
+            let watch = &config.lookup(name)?.watch;
+            if watch.is_empty() {
+                patterns.clear();
+                break;
+            }
+            patterns.extend(watch.iter().cloned());
+        }
+        let mut watcher = Watcher::new(patterns).context("Error watching files")?;
+        let cancel = Cancel::default();
+        self.cancel = Some(cancel.clone());
+
+        // Polling in the background, so that running commands are cancelled as soon as files change
+        let (sender, receiver) = mpsc::channel();
+        let flag = cancel.clone();
+        thread::spawn(move || {
+            loop {
+                let changed = watcher.wait();
+                let failed = changed.is_err();
+                if !failed {
+                    flag.cancel();
+                }
+                if sender.send(changed).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            self.timings.reset();
+            let result = self.execute(invocations);
+            // Stop watching if interrupted or terminated while commands were running
+            exit_if_signaled();
+            match result {
+                Result::Ok(Status::Cancelled) => {}
+                Result::Ok(status) => {
+                    self.timings.summarize();
+                    eprintln!("Recipes finished with {status}, waiting for changes");
+                }
+                Err(err) => eprintln!("Error: {err:?}"),
+            }
+            // Waiting in short intervals, so that signals received while idle stop watching too
+            let changed = loop {
+                exit_if_signaled();
+                match receiver.recv_timeout(SIGNAL_INTERVAL) {
+                    Result::Ok(changed) => break changed,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(err) => return Err(err).context("Error watching files"),
+                }
+            }
+            .context("Error watching files")?;
+            cancel.reset();
+            if self.debug {
+                eprintln!("Changed files: {changed:?}");
+            }
+            eprintln!("Detected changes in {} file(s), rerunning", changed.len());
+        }
+    }
+
+    /// Executes the recipes in sequence, passing captured values along and stopping on first failure.
+    ///
+    /// Returns the status of the failed command, the status of the last command if [`Options::propagate_code`] is set, or success.
+    fn run_sequence(&self, invocations: &[Invocation]) -> Result<Status> {
+        let sequence = Sequence::new(self, invocations);
+        let status = self.runner.execute_all(invocations, &sequence)?;
+        match sequence.last.get() {
+            Some(last) if self.options.propagate_code && status == Status::Exited(0) => Ok(last),
+            _ => Ok(status),
+        }
+    }
+}
+
+/// Hooks into running recipes in sequence, pointing errors at the config file, timing recipes, and replacing the process with the last command.
+struct Sequence<'s, 'a> {
+    /// Session running the recipes.
+    session: &'s Session<'a>,
+    /// Recipes being run.
+    invocations: &'s [Invocation],
+    /// Status of the last recipe, if it was executed.
+    last: Cell<Option<Status>>,
+}
+
+impl<'s, 'a> Sequence<'s, 'a> {
+    /// Creates hooks for running the recipes in the session.
+    const fn new(session: &'s Session<'a>, invocations: &'s [Invocation]) -> Self {
+        Self {
+            session,
+            invocations,
+            last: Cell::new(None),
+        }
+    }
+
+    /// Whether the invocation at the index is the last one.
+    const fn is_last(&self, index: usize) -> bool {
+        index + 1 == self.invocations.len()
+    }
+}
+
+impl RunHooks for Sequence<'_, '_> {
+    fn error(&self, index: usize, err: anyhow::Error) -> anyhow::Error {
+        let name = &self.invocations[index].name;
+        self.session
+            .file
+            .diagnose(self.session.runner.config(), name, err)
+    }
+
+    fn before(&self, index: usize, job: Job) -> Result<Job> {
+        let Session {
+            runner,
+            options,
+            timings,
+            recorder,
+            debug,
+            ..
+        } = self.session;
+        let config = runner.config();
+        let recipe = config.lookup(&self.invocations[index].name)?;
+        let job = self.session.attach(job);
+        // The process is only replaced when nothing is left to do after the command, so not when watching files or timing recipes, nor for inline scripts whose temporary files need removing, jobs whose freshness is checked and recorded, jobs with a timeout to enforce, jobs to retry on failure, jobs accepting exit codes other than `0`, or jobs capturing output for later recipes
+        if cfg!(unix)
+            && !options.watch
+            && !timings.enabled
+            && self.is_last(index)
+            && recipe.exec.unwrap_or(config.exec)
+            && job.script.is_none()
+            && job.freshness.is_none()
+            && job.timeout.is_none()
+            && job.retries == 0
+            && job.success_codes.iter().all(|&code| code == 0)
+            && job.capture.is_none()
+        {
+            if *debug {
+                eprintln!("Replacing process with command");
+            }
+            recorder.record(None);
+            runner.echo_command(&job);
+            exec(&job.command)?;
+        }
+        Ok(job)
+    }
+
+    fn skipped(&self, index: usize) {
+        self.session.timings.skip(index);
+    }
+
+    fn finished(&self, index: usize, job: &Job, status: Status, duration: Duration) {
+        self.session.timings.finish(index, status, duration);
+        if self.session.debug {
+            eprintln!("Command exited with {status}");
+        }
+        if let (Status::TimedOut, Some(timeout)) = (status, job.timeout) {
+            let name = &self.invocations[index].name;
+            eprintln!("Recipe \"{name}\" timed out after {timeout:?}");
+        }
+        if self.is_last(index) {
+            self.last.set(Some(status));
+        }
+    }
+}
 
-    // Resolving the recipe
-    let resolved = recipe
-        .resolve(args)
-        .with_context(|| format!("Error resolving recipe \"{recipe_name}\""))?;
-    if debug {
-        eprintln!("Resolved command: {resolved:?}");
+/// Interval to check whether a signal was received while waiting for changes.
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Gets the signal received while running recipes, see [`jiu::signals::received`].
+#[cfg(unix)]
+fn received_signal() -> Option<i32> {
+    jiu::signals::received()
+}
+
+/// Signals are not handled on other platforms, so none is ever received.
+#[cfg(not(unix))]
+const fn received_signal() -> Option<i32> {
+    None
+}
+
+/// Exits with `128 + signal` if a signal was received, once nothing is left to clean up.
+fn exit_if_signaled() {
+    if let Some(signal) = received_signal() {
+        std::process::exit(128 + signal);
+    }
+}
+
+/// Replaces the process with the command, only returning on error.
+#[cfg(unix)]
+fn exec(resolved: &[String]) -> Result<()> {
+    match jiu::executor::ProcessExecutor.exec(resolved)? {}
+}
+
+/// Replacing the process is not supported, so the command should be spawned instead.
+#[cfg(not(unix))]
+fn exec(_resolved: &[String]) -> Result<()> {
+    Ok(())
+}
+
+/// Prints the notice to standard error, prefixed like output of the job it is about, with echoed commands dimmed if colored.
+fn report(notice: &Notice, color: bool) {
+    match notice {
+        Notice::Retrying {
+            job,
+            status,
+            attempt,
+            attempts,
+        } => {
+            let prefix = job.prefix.as_deref().unwrap_or_default();
+            eprintln!(
+                "{prefix}Command failed with {status}, retrying (attempt {attempt}/{attempts})"
+            );
+        }
+        Notice::Skipped { name, reason } => eprintln!("Skipping recipe \"{name}\": {reason}"),
+        Notice::UpToDate { job, name } => {
+            let prefix = job.prefix.as_deref().unwrap_or_default();
+            eprintln!("{prefix}Skipping recipe \"{name}\": outputs are up to date");
+        }
+        Notice::Echo { job } => {
+            let prefix = job.prefix.as_deref().unwrap_or_default();
+            let command = job.command_line();
+            if color {
+                eprintln!("{prefix}{}", command.dimmed());
+            } else {
+                eprintln!("{prefix}{command}");
+            }
+        }
+    }
+}
+
+/// Checks that the recipe exists, suggesting similar recipes or listing all recipes if not.
+fn check_recipe(config: &Config, name: &str, color: bool) -> Result<()> {
+    if let Err(err) = config.lookup(name) {
+        if matches!(err, Error::RecipeNotFound { .. }) {
+            if let Some(suggestion) = config.suggest(name) {
+                bail!("{err}, did you mean \"{suggestion}\"?");
+            }
+            eprintln!("{}", config.summarize(color));
+        }
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Gets the history of runs, stored in the file at `JIU_HISTORY` if set, or under the data directory of the user otherwise.
+fn history() -> Result<History> {
+    if let Some(path) = env::var_os("JIU_HISTORY") {
+        return Ok(History::new(path.into()));
+    }
+    let dir = dirs::data_dir().context("Error locating data directory for history")?;
+    Ok(History::new(dir.join("jiu").join("history")))
+}
+
+/// Number of recent runs to show with `--history`.
+const HISTORY_SHOWN: usize = 20;
+
+/// Shows recent runs in the directory, oldest first so that the last run is closest to the prompt.
+fn show_history(history: &History, directory: &Path) -> Result<()> {
+    let entries = history.entries_in(directory).with_context(|| {
+        format!(
+            "Error reading history file \"{}\"",
+            history.path().display()
+        )
+    })?;
+    if entries.is_empty() {
+        println!("No recipes have been run in \"{}\"", directory.display());
+    }
+    let now = SystemTime::now();
+    for entry in &entries[entries.len().saturating_sub(HISTORY_SHOWN)..] {
+        let ago = now.duration_since(entry.started).unwrap_or_default();
+        let status = entry
+            .code
+            .map_or_else(|| "exec".to_string(), |code| format!("exit {code}"));
+        println!(
+            "{:>8}  {:>9}  {status:>8}  {}",
+            format_ago(ago),
+            format_secs(entry.duration),
+            entry.command_line()
+        );
+    }
+    Ok(())
+}
+
+/// Formats the time since a run coarsely, like `5m ago`.
+fn format_ago(ago: Duration) -> String {
+    let secs = ago.as_secs();
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Formats a duration in seconds with one decimal, like `1.2s`.
+fn format_secs(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Outcome of a recipe in a run, for the summary of [`Timings`].
+#[derive(Debug, Clone, Copy)]
+enum Outcome {
+    /// Skipped, as its conditions are not met.
+    Skipped,
+    /// Not run, as an earlier recipe failed or the run was cancelled.
+    NotRun,
+    /// Finished with the status after the wall-clock duration.
+    Finished(Status, Duration),
+}
+
+/// Reports the duration of each recipe as it finishes, and summarizes the run, if enabled with `--time` or `JIU_TIMINGS`.
+struct Timings {
+    /// Whether to report anything.
+    enabled: bool,
+    /// When the run started.
+    started: Instant,
+    /// Names and outcomes of recipes, in order of invocation.
+    steps: Mutex<Vec<(String, Outcome)>>,
+}
+
+impl Timings {
+    /// Creates an empty report.
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            steps: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds a recipe to run, in order of invocation.
+    fn add(&mut self, name: String) {
+        self.steps_mut().push((name, Outcome::NotRun));
+    }
+
+    /// Starts over for another run, e.g. when watching files.
+    fn reset(&mut self) {
+        self.started = Instant::now();
+        for (_, outcome) in self.steps_mut().iter_mut() {
+            *outcome = Outcome::NotRun;
+        }
     }
 
-    // Executing the command
-    let status = std::process::Command::new(&resolved[0])
-        .args(&resolved[1..])
-        .spawn()
-        .with_context(|| format!("Error spawning command \"{resolved:?}\""))?
-        .wait()
-        .with_context(|| format!("Error waiting for command \"{resolved:?}\""))?;
+    /// Records that the recipe with the given index was skipped, as its conditions are not met.
+    fn skip(&self, step: usize) {
+        let mut steps = self.steps.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, outcome)) = steps.get_mut(step) {
+            *outcome = Outcome::Skipped;
+        }
+    }
+
+    /// Records that the recipe with the given index finished, reporting its duration.
+    fn finish(&self, step: usize, status: Status, duration: Duration) {
+        let mut steps = self.steps.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some((name, outcome)) = steps.get_mut(step) else {
+            return;
+        };
+        *outcome = Outcome::Finished(status, duration);
+        let name = name.clone();
+        drop(steps);
+        if self.enabled && status != Status::UpToDate {
+            eprintln!(
+                "Recipe \"{name}\" finished with {status} in {}",
+                format_secs(duration)
+            );
+        }
+    }
+
+    /// Prints a table of the outcome and duration of each recipe, and the total duration.
+    fn summarize(&self) {
+        if !self.enabled {
+            return;
+        }
+        let steps = self
+            .steps
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let width = steps
+            .iter()
+            .map(|(name, _)| name.len())
+            .fold("Total".len(), usize::max);
+        eprintln!("Summary:");
+        for (name, outcome) in &steps {
+            let (duration, result) = match outcome {
+                Outcome::Skipped => (String::new(), "skipped, conditions not met".to_string()),
+                Outcome::NotRun => (String::new(), "not run".to_string()),
+                Outcome::Finished(Status::UpToDate, _) => {
+                    (String::new(), "skipped, outputs up to date".to_string())
+                }
+                Outcome::Finished(status, duration) => (format_secs(*duration), status.to_string()),
+            };
+            eprintln!("  {name:<width$}  {duration:>7}  {result}");
+        }
+        eprintln!(
+            "  {:<width$}  {:>7}",
+            "Total",
+            format_secs(self.started.elapsed())
+        );
+    }
 
-    if debug {
-        eprintln!("Command exited with {status}");
+    /// Gets the steps without locking, as the report is not shared yet.
+    fn steps_mut(&mut self) -> &mut Vec<(String, Outcome)> {
+        self.steps.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Records the run to the history once it finishes, or before replacing the process.
+#[derive(Default)]
+struct Recorder {
+    /// History to record to, or `None` to not record.
+    history: Option<History>,
+    /// The run to record, without its duration and exit code.
+    entry: Option<Entry>,
+    /// When the run started.
+    started: Option<Instant>,
+}
+
+impl Recorder {
+    /// Creates a recorder for running the invocations from the directory.
+    fn new(history: Option<History>, directory: &Path, invocations: &[Invocation]) -> Self {
+        let entry = Entry {
+            started: SystemTime::now(),
+            duration: Duration::ZERO,
+            directory: directory.to_path_buf(),
+            invocations: invocations.to_vec(),
+            code: None,
+        };
+        Self {
+            history,
+            entry: Some(entry),
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Records the run with the exit code, or `None` if the process is replaced. Failing to record only prints a warning.
+    fn record(&self, code: Option<i32>) {
+        let (Some(history), Some(entry)) = (&self.history, &self.entry) else {
+            return;
+        };
+        let entry = Entry {
+            duration: self
+                .started
+                .map(|started| started.elapsed())
+                .unwrap_or_default(),
+            code,
+            ..entry.clone()
+        };
+        if let Err(err) = history.record(&entry) {
+            eprintln!(
+                "Warning: Error recording history to \"{}\": {err}",
+                history.path().display()
+            );
+        }
+    }
+}
+
+/// A located config file, kept for rendering diagnostics.
+struct ConfigFile {
+    /// Path to the config file.
+    path: PathBuf,
+    /// Content of the config file.
+    source: String,
+}
+
+impl ConfigFile {
+    /// Gets the directory containing the config file.
+    fn directory(&self) -> &Path {
+        self.path.parent().unwrap_or(&self.path)
+    }
+
+    /// Points the error at the config file with a code excerpt, if it can be located in the recipe.
+    fn diagnose(&self, config: &Config, recipe_name: &str, err: anyhow::Error) -> anyhow::Error {
+        let located = err.downcast_ref::<Error>().and_then(|jiu_err| {
+            let span = config.lookup(recipe_name).ok()?.locate(jiu_err)?;
+            let path = self.path.display().to_string();
+            Some(diagnostic::render(
+                &self.source,
+                &path,
+                &span,
+                &jiu_err.to_string(),
+            ))
+        });
+        located.map_or(err, |message| {
+            anyhow!(message).context(format!("Error resolving recipe \"{recipe_name}\""))
+        })
     }
-    std::process::exit(status.code().unwrap_or(1));
 }
 
 /// Locate config file in the current directory and its parents. To be specific:
@@ -89,30 +737,40 @@ fn main() -> Result<()> {
 /// 1. Find the closest parent directory that contains a `.jiu.toml` file.
 /// 2. Deserialize the file into a [`Config`] struct.
 /// 3. Set working directory to the directory containing the config file.
-fn locate_config_file(debug: bool) -> Result<Config> {
+fn locate_config_file(debug: bool) -> Result<(Config, ConfigFile)> {
     let mut path = env::current_dir()?;
     loop {
         let config_path = path.join(".jiu.toml");
         if config_path.exists() {
-            let config = fs::read_to_string(&config_path)
-                .with_context(|| format!("Error reading config file \"{config_path:?}\""))?;
+            let source = fs::read_to_string(&config_path).with_context(|| {
+                format!("Error reading config file \"{}\"", config_path.display())
+            })?;
             if debug {
-                eprintln!("Found config file: {config_path:?}");
+                eprintln!("Found config file: {}", config_path.display());
             }
-            let config: Config = toml::de::from_str(&config)
-                .with_context(|| format!("Error deserializing config file \"{config_path:?}\""))?;
+            let config = Config::from_toml(&source).with_context(|| {
+                format!(
+                    "Error deserializing config file \"{}\"",
+                    config_path.display()
+                )
+            })?;
             if debug {
                 eprintln!("Deserialized config: {config:#?}");
             }
 
             // Set the working directory to the directory containing the config file
-            env::set_current_dir(&path)
-                .with_context(|| format!("Error setting working directory to \"{path:?}\""))?;
+            env::set_current_dir(&path).with_context(|| {
+                format!("Error setting working directory to \"{}\"", path.display())
+            })?;
             if debug {
-                eprintln!("Set working directory to: {path:?}");
+                eprintln!("Set working directory to: {}", path.display());
             }
 
-            return Ok(config);
+            let file = ConfigFile {
+                path: config_path,
+                source,
+            };
+            return Ok((config, file));
         }
         if !path.pop() {
             break;
@@ -129,12 +787,22 @@ fn help(program_name: &str) {
         env!("CARGO_PKG_DESCRIPTION")
     );
     println!();
-    println!("Usage: {program_name} [OPTION_OR_RECIPE] [ARGS]...");
+    println!("Usage: {program_name} [OPTION_OR_RECIPE] [ARGS]... [+ RECIPE [ARGS]...]...");
     println!();
     println!("Options:");
     println!("  -h, --help       Show this help message");
     println!("  -v, --version    Show version information");
     println!("  -l, --list       List all available recipes");
+    println!("  --history        List recent runs in this project");
+    println!("  --last           Rerun the last run in this project");
+    println!("  -j, --jobs N     Run up to N recipes concurrently");
+    println!(
+        "  --propagate-code Exit with the real code of the last recipe, even if accepted as success"
+    );
+    println!("  -f, --force      Run recipes even if their outputs are up to date");
+    println!("  -w, --watch      Rerun recipes whenever watched files change");
+    println!("  --time           Report the duration of each recipe and summarize the run");
+    println!("  -x, --echo       Print commands of all recipes before running them");
     println!();
 }
 
@@ -0,0 +1,644 @@
+//! Module for running recipes.
+
+use crate::{
+    Config, Error, Freshness,
+    arguments::ArgumentDefinition,
+    executor::{Executor, Job, ProcessExecutor, Script, Status},
+};
+use anyhow::Result;
+use owo_colors::{AnsiColors, OwoColorize};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    num::NonZeroUsize,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Interval to check whether a signal was received while waiting between attempts.
+const SIGNAL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Colors to tell apart output of commands executed concurrently.
+const PALETTE: [AnsiColors; 6] = [
+    AnsiColors::Cyan,
+    AnsiColors::Magenta,
+    AnsiColors::Yellow,
+    AnsiColors::Green,
+    AnsiColors::Blue,
+    AnsiColors::Red,
+];
+
+/// An invocation of a recipe with arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// Name of the recipe.
+    pub name: String,
+    /// Arguments to the recipe.
+    pub args: VecDeque<String>,
+}
+
+impl Invocation {
+    /// Creates a new invocation.
+    #[must_use]
+    pub const fn new(name: String, args: VecDeque<String>) -> Self {
+        Self { name, args }
+    }
+}
+
+/// Something happening while running recipes that the user may want to know about, reported with [`Runner::on_notice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notice<'n> {
+    /// The job failed and is about to be rerun, see [`Job::retries`].
+    Retrying {
+        /// The failed job.
+        job: &'n Job,
+        /// Status of the failed attempt.
+        status: Status,
+        /// Number of the attempt about to be made, starting from `2`.
+        attempt: u32,
+        /// Total number of attempts to make.
+        attempts: u32,
+    },
+    /// The recipe is skipped, as its [`Recipe::when`](crate::Recipe::when) conditions are not met.
+    Skipped {
+        /// Name the recipe was invoked with.
+        name: &'n str,
+        /// The first condition not met.
+        reason: &'n str,
+    },
+    /// The job is skipped, as its outputs are up to date with its sources, see [`Job::freshness`].
+    UpToDate {
+        /// The skipped job.
+        job: &'n Job,
+        /// First name of the recipe, see [`Freshness::name`].
+        name: &'n str,
+    },
+    /// The job is about to be executed and should be echoed, see [`Runner::echo`] and [`Job::echo`].
+    Echo {
+        /// The job to execute, whose command can be printed with [`Job::command_line`].
+        job: &'n Job,
+    },
+}
+
+/// Hooks into running invocations in sequence with [`Runner::check_all`] and [`Runner::execute_all`], each given the index of the invocation. All of them do nothing by default.
+pub trait RunHooks {
+    /// Converts an error checking or resolving the invocation, e.g. to point at the config file.
+    fn error(&self, _index: usize, err: anyhow::Error) -> anyhow::Error {
+        err
+    }
+
+    /// Called with the job of the invocation right before executing it, returning the job to execute instead.
+    ///
+    /// ## Errors
+    ///
+    /// If the job should not be executed, stopping the sequence with the error.
+    fn before(&self, _index: usize, job: Job) -> Result<Job> {
+        Ok(job)
+    }
+
+    /// Called when the invocation is skipped, as its conditions are not met.
+    fn skipped(&self, _index: usize) {}
+
+    /// Called when the job of the invocation finished with the status, after the wall-clock duration.
+    fn finished(&self, _index: usize, _job: &Job, _status: Status, _duration: Duration) {}
+}
+
+/// No hooks.
+impl RunHooks for () {}
+
+/// Callback to report notices with.
+type NoticeHandler<'a> = &'a (dyn Fn(&Notice) + Sync);
+
+/// Runs recipes from a [`Config`] with an [`Executor`].
+#[derive(Clone)]
+pub struct Runner<'a, E = ProcessExecutor> {
+    /// The configuration to look up recipes from.
+    config: &'a Config,
+    /// The executor to execute commands with.
+    executor: E,
+    /// Whether to execute jobs even if their outputs are up to date.
+    force: bool,
+    /// Whether to print commands of all jobs before executing them, regardless of [`Job::echo`].
+    echo: bool,
+    /// Callback to report notices with, if any.
+    on_notice: Option<NoticeHandler<'a>>,
+}
+
+impl<E: fmt::Debug> fmt::Debug for Runner<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Runner")
+            .field("config", &self.config)
+            .field("executor", &self.executor)
+            .field("force", &self.force)
+            .field("echo", &self.echo)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Runner<'a> {
+    /// Creates a new runner for the given configuration, spawning child processes.
+    #[must_use]
+    pub const fn new(config: &'a Config) -> Self {
+        Self::with_executor(config, ProcessExecutor)
+    }
+}
+
+impl<'a, E: Executor> Runner<'a, E> {
+    /// Creates a new runner for the given configuration with a custom executor.
+    #[must_use]
+    pub const fn with_executor(config: &'a Config, executor: E) -> Self {
+        Self {
+            config,
+            executor,
+            force: false,
+            echo: false,
+            on_notice: None,
+        }
+    }
+
+    /// Sets whether to execute jobs even if their outputs are up to date, see [`Job::freshness`].
+    #[must_use]
+    pub const fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Sets whether to echo commands of all jobs before executing them with [`Notice::Echo`], even if [`Job::echo`] is not set.
+    #[must_use]
+    pub const fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Sets the callback to report notices with, like retries of failed jobs. Notices are not reported anywhere without it.
+    #[must_use]
+    pub const fn on_notice(mut self, on_notice: NoticeHandler<'a>) -> Self {
+        self.on_notice = Some(on_notice);
+        self
+    }
+
+    /// Gets the configuration of the runner.
+    #[must_use]
+    pub const fn config(&self) -> &'a Config {
+        self.config
+    }
+
+    /// Gets the executor of the runner.
+    #[must_use]
+    pub const fn executor(&self) -> &E {
+        &self.executor
+    }
+
+    /// Resolves the recipe with the given name to a command.
+    ///
+    /// ## Errors
+    ///
+    /// - If the recipe could not be looked up, see [`Config::lookup`].
+    /// - If the recipe could not be resolved with the given arguments.
+    ///
+    /// The underlying [`Error`](crate::Error) can be retrieved with [`anyhow::Error::downcast_ref`].
+    pub fn resolve(&self, name: &str, args: VecDeque<String>) -> Result<Vec<String>> {
+        self.config
+            .lookup(name)?
+            .resolve(args)
+            .map_err(|err| resolution_error(name, err))
+    }
+
+    /// Checks that the recipe with the given name resolves with values captured so far, without executing command substitutions, see [`Recipe::check_with`](crate::Recipe::check_with).
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::resolve`].
+    pub fn check_with(
+        &self,
+        name: &str,
+        args: VecDeque<String>,
+        captured: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.config
+            .lookup(name)?
+            .check_with(args, captured)
+            .map_err(|err| resolution_error(name, err))
+    }
+
+    /// Resolves the recipe with the given name to a job, carrying options of the recipe on how to execute it.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::resolve`].
+    pub fn prepare(&self, name: &str, args: VecDeque<String>) -> Result<Job> {
+        self.prepare_with(name, args, &HashMap::new())
+    }
+
+    /// Resolves the recipe with the given name to a job like [`Runner::prepare`], with values captured from earlier recipes.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::resolve`] and [`Recipe::resolve_with`](crate::Recipe::resolve_with).
+    pub fn prepare_with(
+        &self,
+        name: &str,
+        args: VecDeque<String>,
+        captured: &HashMap<String, String>,
+    ) -> Result<Job> {
+        let recipe = self.config.lookup(name)?;
+        let command = recipe
+            .resolve_with(args, captured)
+            .map_err(|err| resolution_error(name, err))?;
+        Ok(Job {
+            command,
+            timeout: recipe.timeout,
+            retries: recipe.retries,
+            retry_delay: recipe.retry_delay.unwrap_or_default(),
+            success_codes: recipe.success_codes.clone(),
+            capture: recipe.capture.clone(),
+            script: recipe.script.as_ref().map(|body| Script {
+                body: body.clone(),
+                interpreter: recipe.interpreter.clone(),
+            }),
+            freshness: (!recipe.sources.is_empty() || !recipe.outputs.is_empty()).then(|| {
+                Freshness {
+                    name: recipe.names.first().cloned().unwrap_or_default(),
+                    sources: recipe.sources.clone(),
+                    outputs: recipe.outputs.clone(),
+                    method: recipe.up_to_date,
+                    cache_dir: None,
+                }
+            }),
+            echo: recipe.echo.unwrap_or(self.config.echo),
+            ..Job::default()
+        })
+    }
+
+    /// Executes a job with the executor, waiting for it to finish.
+    ///
+    /// A failed command is rerun up to [`Job::retries`] times unless cancelled, returning the status of the last attempt. If the outputs of the job are up to date, it is skipped with [`Status::UpToDate`], unless forced with [`Runner::force`].
+    ///
+    /// ## Errors
+    ///
+    /// If the command could not be executed, or its freshness could not be checked or recorded.
+    pub fn execute(&self, job: &Job) -> Result<Status> {
+        if self.up_to_date(job)? {
+            return Ok(Status::UpToDate);
+        }
+        self.echo_command(job);
+        let status = self.retry(job, || self.executor.execute(job))?;
+        Self::record(job, status)?;
+        Ok(status)
+    }
+
+    /// Executes a job like [`Runner::execute`], capturing its trimmed standard output into `captured` if [`Job::capture`] is set and the job succeeded.
+    ///
+    /// Jobs capturing output are never skipped as up to date, since later jobs need the captured value.
+    ///
+    /// ## Errors
+    ///
+    /// If the command could not be executed, or its output could not be captured.
+    pub fn execute_capturing(
+        &self,
+        job: &Job,
+        captured: &mut HashMap<String, String>,
+    ) -> Result<Status> {
+        let Some(name) = &job.capture else {
+            return self.execute(job);
+        };
+        self.echo_command(job);
+        let mut output = String::new();
+        let status = self.retry(job, || {
+            let (status, out) = self.executor.capture(job)?;
+            output = out;
+            Ok(status)
+        })?;
+        Self::record(job, status)?;
+        if job.accepts(status) {
+            captured.insert(name.clone(), output.trim().to_string());
+        }
+        Ok(status)
+    }
+
+    /// Reports [`Notice::Echo`] for the job, if echoing is enabled by [`Runner::echo`] or [`Job::echo`].
+    pub fn echo_command(&self, job: &Job) {
+        if self.echo || job.echo {
+            self.notify(&Notice::Echo { job });
+        }
+    }
+
+    /// Checks whether the job should be skipped, as its outputs are up to date and it is not forced, reporting [`Notice::UpToDate`] if so.
+    fn up_to_date(&self, job: &Job) -> Result<bool> {
+        let Some(freshness) = &job.freshness else {
+            return Ok(false);
+        };
+        if self.force || job.capture.is_some() || !freshness.is_up_to_date(job)? {
+            return Ok(false);
+        }
+        self.notify(&Notice::UpToDate {
+            job,
+            name: &freshness.name,
+        });
+        Ok(true)
+    }
+
+    /// Records the freshness of the job if it succeeded, see [`Freshness::record`].
+    fn record(job: &Job, status: Status) -> Result<()> {
+        match &job.freshness {
+            Some(freshness) if job.accepts(status) => freshness.record(job),
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes attempts to execute the job until it succeeds or [`Job::retries`] are exhausted, reporting each retry.
+    ///
+    /// Commands cancelled or interrupted by the user are never retried, see [`interrupted`].
+    fn retry(&self, job: &Job, mut run: impl FnMut() -> Result<Status>) -> Result<Status> {
+        let attempts = job.retries.saturating_add(1);
+        let mut status = run()?;
+        for attempt in 2..=attempts {
+            if job.accepts(status) || status == Status::Cancelled || interrupted(status) {
+                break;
+            }
+            self.notify(&Notice::Retrying {
+                job,
+                status,
+                attempt,
+                attempts,
+            });
+            if !pause(job.retry_delay) {
+                break;
+            }
+            status = run()?;
+        }
+        Ok(status)
+    }
+
+    /// Reports the notice with the callback set by [`Runner::on_notice`], if any.
+    fn notify(&self, notice: &Notice) {
+        if let Some(on_notice) = self.on_notice {
+            on_notice(notice);
+        }
+    }
+
+    /// Checks whether the recipe with the given name should be skipped, as its [`Recipe::when`](crate::Recipe::when) conditions are not met, reporting [`Notice::Skipped`] if so.
+    ///
+    /// ## Errors
+    ///
+    /// If the recipe could not be looked up, see [`Config::lookup`].
+    pub fn skip(&self, name: &str) -> Result<bool> {
+        let recipe = self.config.lookup(name)?;
+        if let Err(reason) = recipe.when.check() {
+            self.notify(&Notice::Skipped {
+                name,
+                reason: &reason,
+            });
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Resolves and executes the recipe with the given name, unless it should be skipped.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::skip`], [`Runner::prepare`] and [`Runner::execute`].
+    pub fn run(&self, name: &str, args: VecDeque<String>) -> Result<Status> {
+        if self.skip(name)? {
+            return Ok(Status::Exited(0));
+        }
+        let job = self.prepare(name, args)?;
+        self.execute(&job)
+    }
+
+    /// Resolves and executes the invocations in sequence, skipping as in [`Runner::skip`] and stopping on first failure, see [`Job::accepts`], or once a signal is received, see [`signals::received`](crate::signals::received).
+    ///
+    /// All invocations are checked with [`Runner::check_all`] before any of them is executed with [`Runner::execute_all`], so nothing runs if an invocation could not be resolved. Returns the status of the failed invocation, or success if all succeeded.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::check_all`] and [`Runner::execute_all`].
+    pub fn run_all(&self, invocations: &[Invocation]) -> Result<Status> {
+        self.check_all(invocations, &())?;
+        self.execute_all(invocations, &())
+    }
+
+    /// Checks that the invocations whose conditions are met resolve, with values captured by earlier invocations not known yet, see [`Runner::check_with`].
+    ///
+    /// ## Errors
+    ///
+    /// If any recipe could not be looked up or resolved, converted with [`RunHooks::error`].
+    pub fn check_all(&self, invocations: &[Invocation], hooks: &impl RunHooks) -> Result<()> {
+        let mut pending = HashMap::new();
+        for (i, Invocation { name, args }) in invocations.iter().enumerate() {
+            let recipe = self
+                .config
+                .lookup(name)
+                .map_err(|err| hooks.error(i, err.into()))?;
+            // Conditions are evaluated right before executing, so invocations not meeting them yet are only checked then
+            if recipe.when.is_met() {
+                self.check_with(name, args.clone(), &pending)
+                    .map_err(|err| hooks.error(i, err))?;
+                if let Some(capture) = &recipe.capture {
+                    pending.insert(capture.clone(), String::new());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes the invocations in sequence, stopping like [`Runner::run_all`], without checking them first.
+    ///
+    /// Each invocation is skipped or resolved right before executing it, with values captured by earlier ones, so that conditions see the effects of earlier invocations and command substitutions are executed once.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Runner::prepare_with`], [`Runner::execute_capturing`] and [`RunHooks::before`], with resolution errors converted with [`RunHooks::error`].
+    pub fn execute_all(&self, invocations: &[Invocation], hooks: &impl RunHooks) -> Result<Status> {
+        let mut captured = HashMap::new();
+        for (i, Invocation { name, args }) in invocations.iter().enumerate() {
+            if let Some(signal) = received_signal() {
+                return Ok(Status::Signaled(signal));
+            }
+            if self.skip(name)? {
+                hooks.skipped(i);
+                continue;
+            }
+            let job = self
+                .prepare_with(name, args.clone(), &captured)
+                .map_err(|err| hooks.error(i, err))?;
+            let job = hooks.before(i, job)?;
+            let started = Instant::now();
+            let status = self.execute_capturing(&job, &mut captured)?;
+            hooks.finished(i, &job, status, started.elapsed());
+            // Stop if interrupted, even if the command handled the signal and succeeded
+            if let Some(signal) = received_signal() {
+                return Ok(Status::Signaled(signal));
+            }
+            if !job.accepts(status) {
+                return Ok(status);
+            }
+        }
+        Ok(Status::Exited(0))
+    }
+
+    /// Executes labelled jobs concurrently with at most `max_jobs` at a time, prefixing output with the labels.
+    ///
+    /// After a job fails or a signal is received, remaining jobs are not started, while running ones are waited for. Returns the status of the first failed job in order, the received signal, or success if all succeeded.
+    ///
+    /// ## Errors
+    ///
+    /// If any command could not be executed.
+    ///
+    /// ## Panics
+    ///
+    /// If the executor panics.
+    pub fn execute_parallel(
+        &self,
+        jobs: &[(String, Job)],
+        max_jobs: NonZeroUsize,
+        color: bool,
+    ) -> Result<Status>
+    where
+        E: Sync,
+    {
+        self.execute_parallel_with(jobs, max_jobs, color, |_, _, _| {})
+    }
+
+    /// Executes labelled jobs concurrently like [`Runner::execute_parallel`], calling `on_finish` with the index, status and wall-clock duration of each job once it finishes.
+    ///
+    /// ## Errors
+    ///
+    /// If any command could not be executed.
+    ///
+    /// ## Panics
+    ///
+    /// If the executor or `on_finish` panics.
+    pub fn execute_parallel_with(
+        &self,
+        jobs: &[(String, Job)],
+        max_jobs: NonZeroUsize,
+        color: bool,
+        on_finish: impl Fn(usize, Status, Duration) + Sync,
+    ) -> Result<Status>
+    where
+        E: Sync,
+    {
+        // Pad labels to the same width, coloring each differently
+        let width = jobs.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        let jobs: Vec<Job> = jobs
+            .iter()
+            .enumerate()
+            .map(|(i, (label, job))| {
+                let label = format!("{label:<width$}");
+                let label = if color {
+                    label.color(PALETTE[i % PALETTE.len()]).to_string()
+                } else {
+                    label
+                };
+                Job {
+                    prefix: Some(format!("{label} | ")),
+                    ..job.clone()
+                }
+            })
+            .collect();
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let results = Mutex::new(Vec::with_capacity(jobs.len()));
+        thread::scope(|scope| {
+            for _ in 0..max_jobs.get().min(jobs.len()) {
+                scope.spawn(|| {
+                    loop {
+                        if failed.load(Ordering::SeqCst) || received_signal().is_some() {
+                            break;
+                        }
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(job) = jobs.get(i) else {
+                            break;
+                        };
+                        let started = Instant::now();
+                        let result = self.execute(job);
+                        if let Ok(status) = result {
+                            on_finish(i, status, started.elapsed());
+                        }
+                        if !result.as_ref().is_ok_and(|&status| job.accepts(status)) {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        results.lock().expect("Lock poisoned").push((i, result));
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().expect("Lock poisoned");
+        results.sort_by_key(|(i, _)| *i);
+        for (i, result) in results {
+            let status = result?;
+            if !jobs[i].accepts(status) {
+                return Ok(status);
+            }
+        }
+        Ok(received_signal().map_or(Status::Exited(0), Status::Signaled))
+    }
+}
+
+/// Gets the signal received by this process, so that no more commands are started, see [`signals::received`](crate::signals::received).
+#[cfg(unix)]
+fn received_signal() -> Option<i32> {
+    crate::signals::received()
+}
+
+/// Signals are not handled on other platforms, so none is ever received.
+#[cfg(not(unix))]
+const fn received_signal() -> Option<i32> {
+    None
+}
+
+/// Checks whether the user asked to stop, as this process received a signal forwarded to commands, or the command was terminated by a signal asking it to stop.
+#[cfg(unix)]
+fn interrupted(status: Status) -> bool {
+    use libc::{SIGHUP, SIGINT, SIGTERM};
+    received_signal().is_some() || matches!(status, Status::Signaled(SIGINT | SIGTERM | SIGHUP))
+}
+
+/// Signals are not forwarded to commands on other platforms, so they are never interrupted by the user.
+#[cfg(not(unix))]
+const fn interrupted(_status: Status) -> bool {
+    false
+}
+
+/// Sleeps for the duration, returning `false` early if a signal is received.
+fn pause(duration: Duration) -> bool {
+    let deadline = Instant::now().checked_add(duration);
+    loop {
+        if received_signal().is_some() {
+            return false;
+        }
+        let remaining = deadline.map_or(SIGNAL_INTERVAL, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+        if remaining.is_zero() {
+            return true;
+        }
+        thread::sleep(remaining.min(SIGNAL_INTERVAL));
+    }
+}
+
+/// Adds the recipe being resolved to the error as context, and the argument if it is missing.
+fn resolution_error(name: &str, err: Error) -> anyhow::Error {
+    let argument = match &err {
+        Error::MissingArgument { name, arg_type } => Some(
+            ArgumentDefinition::new(name.clone(), arg_type.clone())
+                .summarize(false)
+                .0,
+        ),
+        _ => None,
+    };
+    let err = anyhow::Error::new(err);
+    let err = match argument {
+        Some(argument) => err.context(format!("While resolving argument \"{argument}\"")),
+        None => err,
+    };
+    err.context(format!("Error resolving recipe \"{name}\""))
+}
@@ -0,0 +1,89 @@
+//! Module for forwarding signals to child processes on Unix.
+
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::{
+    io,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicI32, Ordering},
+    },
+    thread,
+};
+
+/// Process IDs of running child processes.
+static CHILDREN: Mutex<Vec<libc::pid_t>> = Mutex::new(Vec::new());
+
+/// The last signal received, or `0` if none.
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+/// Forwards `SIGINT`, `SIGTERM` and `SIGHUP` received by this process to running child processes, instead of terminating immediately.
+///
+/// Received signals are recorded for [`received`] instead of terminating this process, so that callers stop running commands and exit with `128 + signal` themselves, after cleaning up. `SIGINT` is not forwarded when this process is in the foreground process group of the terminal, since the terminal has already sent it to the whole group, child processes included.
+///
+/// ## Errors
+///
+/// If the signal handlers could not be registered.
+pub fn forward_signals() -> io::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            RECEIVED.store(signal, Ordering::SeqCst);
+            let children = CHILDREN.lock().unwrap_or_else(PoisonError::into_inner);
+            if signal == SIGINT && in_foreground() {
+                continue;
+            }
+            for &pid in children.iter() {
+                // SAFETY: `kill` has no memory safety requirements
+                unsafe {
+                    libc::kill(pid, signal);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Gets the last signal received, so that callers can stop instead of running more commands.
+#[must_use]
+pub fn received() -> Option<i32> {
+    Some(RECEIVED.load(Ordering::SeqCst)).filter(|&signal| signal != 0)
+}
+
+/// Checks whether this process is in the foreground process group of the terminal.
+fn in_foreground() -> bool {
+    // SAFETY: Both functions have no memory safety requirements, and `tcgetpgrp` fails with -1 if stdin is not a terminal
+    unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+}
+
+/// Registers a running child process to forward signals to, until dropped.
+#[derive(Debug)]
+pub(crate) struct ChildGuard(Option<libc::pid_t>);
+
+impl ChildGuard {
+    /// Registers the child process with the given ID.
+    pub(crate) fn new(pid: u32) -> Self {
+        // Never register an invalid ID, since non-positive IDs would signal process groups
+        let pid = libc::pid_t::try_from(pid).ok().filter(|&pid| pid > 0);
+        if let Some(pid) = pid {
+            CHILDREN
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(pid);
+        }
+        Self(pid)
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            CHILDREN
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|&child| child != pid);
+        }
+    }
+}
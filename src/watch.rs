@@ -0,0 +1,163 @@
+//! Module for watching files for changes by polling.
+
+use crate::freshness::expand;
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// Interval to poll files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time without further changes to wait for before reporting changes, so that saving many files at once is reported once.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Directories never watched, as they are written by tools including `jiu` itself.
+const EXCLUDED: &[&str] = &[".git", ".jiu"];
+
+/// Modification times of watched files.
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Watches files matching glob patterns for changes by polling their modification times.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    /// Glob patterns of files to watch, or all files in the working directory not ignored by `.gitignore` if empty.
+    patterns: Vec<String>,
+    /// Modification times of files when last checked.
+    snapshot: Snapshot,
+}
+
+impl Watcher {
+    /// Creates a new watcher, taking the current state of the files as unchanged.
+    ///
+    /// ## Errors
+    ///
+    /// If a pattern is invalid, or the working directory could not be walked.
+    pub fn new(patterns: Vec<String>) -> Result<Self> {
+        let mut watcher = Self {
+            patterns,
+            snapshot: Snapshot::new(),
+        };
+        watcher.snapshot = watcher.scan()?;
+        Ok(watcher)
+    }
+
+    /// Blocks until files are created, modified or removed, returning the changed paths once no further changes are seen for a short while.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Watcher::new`].
+    pub fn wait(&mut self) -> Result<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+        while changed.is_empty() {
+            thread::sleep(POLL_INTERVAL);
+            changed = self.poll()?;
+        }
+        loop {
+            thread::sleep(DEBOUNCE);
+            let more = self.poll()?;
+            if more.is_empty() {
+                return Ok(changed.into_iter().collect());
+            }
+            changed.extend(more);
+        }
+    }
+
+    /// Checks files once, returning the paths changed since the last check.
+    fn poll(&mut self) -> Result<BTreeSet<PathBuf>> {
+        let snapshot = self.scan()?;
+        let mut changed: BTreeSet<PathBuf> = snapshot
+            .iter()
+            .filter(|&(path, time)| self.snapshot.get(path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            self.snapshot
+                .keys()
+                .filter(|path| !snapshot.contains_key(*path))
+                .cloned(),
+        );
+        self.snapshot = snapshot;
+        Ok(changed)
+    }
+
+    /// Collects modification times of the watched files.
+    fn scan(&self) -> Result<Snapshot> {
+        let paths = if self.patterns.is_empty() {
+            walk()?
+        } else {
+            let mut paths = Vec::new();
+            for pattern in &self.patterns {
+                paths.extend(expand(pattern)?);
+            }
+            paths
+        };
+        // Files removed while scanning have no modification time, and are reported as changed
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                let time = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                (path, time)
+            })
+            .collect())
+    }
+}
+
+/// Lists files in the working directory, skipping those ignored by `.gitignore` and [`EXCLUDED`] directories.
+fn walk() -> Result<Vec<PathBuf>> {
+    let walker = WalkBuilder::new(".")
+        .hidden(false)
+        .filter_entry(|entry| {
+            !entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| EXCLUDED.contains(&name))
+        })
+        .build();
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry.context("Error walking working directory")?;
+        if entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_watcher() {
+        let dir = env::temp_dir().join(format!("jiu-test-watch-{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let file = dir.join("main.rs");
+        fs::write(&file, "").expect("Failed to write file");
+        let mut watcher = Watcher::new(vec![format!("{}/*.rs", dir.display())])
+            .expect("Failed to create watcher");
+        assert!(watcher.poll().expect("Failed to poll").is_empty());
+
+        let created = dir.join("lib.rs");
+        fs::write(&created, "").expect("Failed to write file");
+        fs::write(dir.join("notes.txt"), "").expect("Failed to write file");
+        assert_eq!(watcher.wait().expect("Failed to wait"), vec![created]);
+
+        fs::remove_file(&file).expect("Failed to remove file");
+        assert_eq!(
+            watcher.poll().expect("Failed to poll"),
+            BTreeSet::from([file])
+        );
+
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+    }
+}
@@ -1,5 +1,29 @@
-use jiu::Config;
-use std::collections::VecDeque;
+use jiu::{
+    Config, Error, Executor, Invocation, Job, Notice, RunHooks, Runner, Status,
+    executor::{ProcessExecutor, RecordingExecutor},
+};
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Runs the `jiu` binary with the arguments in a new directory holding the config file, returning its exit code.
+#[cfg(all(unix, feature = "cli"))]
+fn run_binary(name: &str, config_str: &str, args: &[&str]) -> Option<i32> {
+    let dir = std::env::temp_dir().join(format!("jiu-test-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    std::fs::write(dir.join(".jiu.toml"), config_str).expect("Failed to write config file");
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_jiu"))
+        .args(args)
+        .current_dir(&dir)
+        .env("JIU_HISTORY", dir.join("history"))
+        .status()
+        .expect("Failed to run jiu");
+    std::fs::remove_dir_all(&dir).expect("Failed to remove directory");
+    status.code()
+}
 
 /// A macro to create a vector of strings from a list of literals.
 macro_rules! vecs {
@@ -26,3 +50,893 @@ fn test_resolve_1() {
 
     assert_eq!(resolved, vecs!["echo", "Hello", "val1", "val0", "val2"]);
 }
+
+#[test]
+fn test_runner_resolve() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["build", "b"]
+        arguments = ["?profile"]
+        command = ["cargo", "build", ["?profile"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+
+    // Resolving does not consume the recipe
+    let resolved = runner
+        .resolve("b", VecDeque::new())
+        .expect("Failed to resolve recipe");
+    assert_eq!(resolved, vecs!["cargo", "build"]);
+    let resolved = runner
+        .resolve("build", VecDeque::from(vecs!["--release"]))
+        .expect("Failed to resolve recipe");
+    assert_eq!(resolved, vecs!["cargo", "build", "--release"]);
+
+    let err = runner.resolve("nope", VecDeque::new()).unwrap_err();
+    assert_eq!(err.to_string(), "Recipe \"nope\" not found");
+}
+
+#[test]
+fn test_recording_executor() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["test"]
+        arguments = ["*rest"]
+        command = ["cargo", "test", ["*rest"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Exited(101)));
+
+    let status = runner
+        .run("test", VecDeque::from(vecs!["--", "--nocapture"]))
+        .expect("Failed to run recipe");
+    assert_eq!(status, Status::Exited(101));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![vecs!["cargo", "test", "--", "--nocapture"]]
+    );
+}
+
+#[test]
+fn test_retries() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["fetch"]
+        retries = 2
+        retry_delay = "1ms"
+        command = ["curl", "-O", "https://example.com"]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let retries = Mutex::new(Vec::new());
+    let on_notice = |notice: &Notice| {
+        if let Notice::Retrying {
+            status,
+            attempt,
+            attempts,
+            ..
+        } = *notice
+        {
+            retries.lock().unwrap().push((status, attempt, attempts));
+        }
+    };
+    let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Exited(6)))
+        .on_notice(&on_notice);
+
+    let status = runner
+        .run("fetch", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(status, Status::Exited(6));
+    assert_eq!(runner.executor().commands().len(), 3);
+    assert_eq!(
+        *retries.lock().unwrap(),
+        vec![(Status::Exited(6), 2, 3), (Status::Exited(6), 3, 3)]
+    );
+
+    let runner = Runner::with_executor(&config, RecordingExecutor::default());
+    let status = runner
+        .run("fetch", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(runner.executor().commands().len(), 1);
+
+    // Commands terminated by SIGTERM are not retried
+    #[cfg(unix)]
+    {
+        let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Signaled(15)));
+        let status = runner
+            .run("fetch", VecDeque::new())
+            .expect("Failed to run recipe");
+        assert_eq!(status, Status::Signaled(15));
+        assert_eq!(runner.executor().commands().len(), 1);
+    }
+}
+
+#[test]
+fn test_success_codes() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["search"]
+        success_codes = [0, 1]
+        retries = 1
+        command = ["grep", "-r", "TODO"]
+
+        [[recipes]]
+        names = ["after"]
+        command = ["echo", "done"]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Exited(1)));
+    let job = runner
+        .prepare("search", VecDeque::new())
+        .expect("Failed to prepare recipe");
+    assert!(job.accepts(Status::Exited(1)));
+    assert!(!job.accepts(Status::Exited(2)));
+    assert!(!Job::new(vecs!["true"]).accepts(Status::Exited(1)));
+
+    // Executing returns the real status without retrying
+    assert_eq!(runner.execute(&job).unwrap(), Status::Exited(1));
+    assert_eq!(runner.executor().commands().len(), 1);
+
+    // The sequence stops at the recipe without accepted success codes
+    let invocations = vec![
+        Invocation::new("search".to_string(), VecDeque::new()),
+        Invocation::new("after".to_string(), VecDeque::new()),
+        Invocation::new("search".to_string(), VecDeque::new()),
+    ];
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(1));
+    assert_eq!(runner.executor().commands().len(), 3);
+}
+
+#[test]
+fn test_capture() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["describe"]
+        capture = "version"
+        command = ["git", "describe", "--tags"]
+
+        [[recipes]]
+        names = ["set-version"]
+        command = ["cargo", "set-version", ["@version"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::with_executor(
+        &config,
+        RecordingExecutor::default().with_output("v1.2.3\n"),
+    );
+
+    // Captured values must come from earlier recipes
+    let err = runner
+        .run("set-version", VecDeque::new())
+        .expect_err("Expected missing captured value");
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::CaptureMissing {
+            name: "version".to_string()
+        })
+    );
+    let invocations = vec![
+        Invocation::new("set-version".to_string(), VecDeque::new()),
+        Invocation::new("describe".to_string(), VecDeque::new()),
+    ];
+    assert!(runner.run_all(&invocations).is_err());
+    assert!(runner.executor().commands().is_empty());
+
+    let invocations = vec![
+        Invocation::new("describe".to_string(), VecDeque::new()),
+        Invocation::new("set-version".to_string(), VecDeque::new()),
+    ];
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![
+            vecs!["git", "describe", "--tags"],
+            vecs!["cargo", "set-version", "v1.2.3"]
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_capture() {
+    let job = Job::new(vecs!["sh", "-c", "echo '  captured  '; exit 3"]);
+    let (status, output) = ProcessExecutor
+        .capture(&job)
+        .expect("Failed to execute command");
+    assert_eq!(status, Status::Exited(3));
+    assert_eq!(output, "  captured  \n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_substitution() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["tag"]
+        command = ["docker", "build", "--build-arg", ["!sh", "-c", "echo ' abc123 '"]]
+
+        [[recipes]]
+        names = ["broken"]
+        command = ["echo", ["!sh", "-c", "echo oops >&2; exit 2"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+
+    let resolved = runner
+        .resolve("tag", VecDeque::new())
+        .expect("Failed to resolve recipe");
+    assert_eq!(resolved, vecs!["docker", "build", "--build-arg", "abc123"]);
+
+    let err = runner
+        .resolve("broken", VecDeque::new())
+        .expect_err("Expected substitution to fail");
+    let err = err.downcast_ref::<Error>().expect("Expected a jiu error");
+    assert_eq!(
+        err,
+        &Error::SubstitutionFailed {
+            command: vecs!["sh", "-c", "echo oops >&2; exit 2"],
+            message: "exit status: 2: oops".to_string()
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        r#"Command substitution ["sh", "-c", "echo oops >&2; exit 2"] failed: exit status: 2: oops"#
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_substitution_runs_once() {
+    let dir = std::env::temp_dir().join(format!("jiu-test-substitution-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    let log = dir.join("runs.log");
+    let config_str = format!(
+        r#"
+        [[recipes]]
+        names = ["describe"]
+        capture = "version"
+        command = ["git", "describe"]
+
+        [[recipes]]
+        names = ["tag"]
+        command = ["docker", "tag", ["@version"], ["!sh", "-c", "echo run >> \"$0\"; echo abc123", "{}"]]
+    "#,
+        log.display()
+    );
+    let config: Config = toml::from_str(&config_str).expect("Failed to parse config file");
+    let runner = Runner::with_executor(&config, RecordingExecutor::default().with_output("v1"));
+
+    let invocations = vec![
+        Invocation::new("describe".to_string(), VecDeque::new()),
+        Invocation::new("tag".to_string(), VecDeque::new()),
+    ];
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![
+            vecs!["git", "describe"],
+            vecs!["docker", "tag", "v1", "abc123"]
+        ]
+    );
+    let runs = std::fs::read_to_string(&log).expect("Failed to read log");
+    assert_eq!(runs.lines().count(), 1);
+
+    std::fs::remove_dir_all(&dir).expect("Failed to remove directory");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shell() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["greet"]
+        shell = "sh"
+        arguments = ["greeting", "*names"]
+        capture = "output"
+        command = [
+            "shift",
+            "for name in \"$@\"; do echo \"$0: $name\"; done | sort",
+        ]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+    let job = runner
+        .prepare("greet", VecDeque::from(vecs!["hi", "Bob", "Alice Smith"]))
+        .expect("Failed to prepare recipe");
+    let (status, output) = ProcessExecutor
+        .capture(&job)
+        .expect("Failed to execute command");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(output, "greet: Alice Smith\ngreet: Bob\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_script() {
+    let config_str = r##"
+        [[recipes]]
+        names = ["interpreted"]
+        arguments = ["first", "*rest"]
+        interpreter = "sh"
+        script = """
+        echo "$1"
+        shift
+        echo "$#"
+        """
+
+        [[recipes]]
+        names = ["shebang"]
+        script = """#!/bin/sh
+        echo "$0" | grep -q jiu- && echo ok
+        """
+    "##;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+
+    let job = runner
+        .prepare("interpreted", VecDeque::from(vecs!["a b", "c", "d"]))
+        .expect("Failed to prepare recipe");
+    assert_eq!(job.command, vecs!["a b", "c", "d"]);
+    let (status, output) = ProcessExecutor
+        .capture(&job)
+        .expect("Failed to execute script");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(output, "a b\n2\n");
+
+    let job = runner
+        .prepare("shebang", VecDeque::new())
+        .expect("Failed to prepare recipe");
+    let (status, output) = ProcessExecutor
+        .capture(&job)
+        .expect("Failed to execute script");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(output, "ok\n");
+}
+
+#[test]
+fn test_conditions() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["plan9-only"]
+        when = { os = "plan9" }
+        command = ["echo", "plan9"]
+
+        [[recipes]]
+        names = ["open-docs"]
+        when = { exists = "Cargo.toml" }
+        arguments = ["?page"]
+        command.plan9 = ["plumb", ["?page"]]
+        command.default = ["xdg-open", ["?page"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let skipped = Mutex::new(Vec::new());
+    let on_notice = |notice: &Notice| {
+        if let Notice::Skipped { name, reason } = *notice {
+            skipped.lock().unwrap().push(format!("{name}: {reason}"));
+        }
+    };
+    let runner = Runner::with_executor(&config, RecordingExecutor::default()).on_notice(&on_notice);
+    assert!(runner.skip("plan9-only").expect("Failed to look up recipe"));
+    assert!(!runner.skip("open-docs").expect("Failed to look up recipe"));
+    assert_eq!(
+        *skipped.lock().unwrap(),
+        vec![r#"plan9-only: os is not "plan9""#]
+    );
+
+    let invocations = vec![
+        Invocation::new("plan9-only".to_string(), VecDeque::new()),
+        Invocation::new("open-docs".to_string(), VecDeque::from(vecs!["index.html"])),
+    ];
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![vecs!["xdg-open", "index.html"]]
+    );
+
+    let err = toml::from_str::<Config>(
+        r#"
+        [[recipes]]
+        names = ["open-docs"]
+        command.plan9 = ["plumb"]
+        command.haiku = ["open"]
+    "#,
+    )
+    .expect_err("Missing operating system should be rejected");
+    assert!(
+        err.message()
+            .ends_with(r#"and no "default", only for ["haiku", "plan9"]"#),
+        "{err}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_conditions_at_run_time() {
+    let dir = std::env::temp_dir().join(format!("jiu-test-conditions-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    let marker = dir.join("marker");
+    let config_str = format!(
+        r#"
+        [[recipes]]
+        names = ["create"]
+        command = ["touch", "{marker}"]
+
+        [[recipes]]
+        names = ["remove"]
+        when = {{ exists = "{marker}" }}
+        command = ["rm", "{marker}"]
+    "#,
+        marker = marker.display()
+    );
+    let config: Config = toml::from_str(&config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+
+    // The condition of the second recipe is met once the first one has run
+    let invocations = vec![
+        Invocation::new("create".to_string(), VecDeque::new()),
+        Invocation::new("remove".to_string(), VecDeque::new()),
+    ];
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert!(!marker.exists());
+
+    std::fs::remove_dir_all(&dir).expect("Failed to remove directory");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_up_to_date() {
+    let dir = std::env::temp_dir().join(format!("jiu-test-up-to-date-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    std::fs::write(dir.join("schema.in"), "").expect("Failed to write source");
+    let config_str = format!(
+        r#"
+        [[recipes]]
+        names = ["generate"]
+        sources = ["{dir}/*.in"]
+        outputs = ["{dir}/*.out"]
+        command = ["codegen"]
+        "#,
+        dir = dir.display()
+    );
+    let config: Config = toml::from_str(&config_str).expect("Failed to parse config file");
+    let up_to_date = Mutex::new(Vec::new());
+    let on_notice = |notice: &Notice| {
+        if let Notice::UpToDate { name, .. } = *notice {
+            up_to_date.lock().unwrap().push(name.to_string());
+        }
+    };
+    let runner = Runner::with_executor(&config, RecordingExecutor::default()).on_notice(&on_notice);
+
+    // Missing outputs
+    let status = runner
+        .run("generate", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(runner.executor().commands().len(), 1);
+
+    // Outputs newer than sources
+    std::fs::write(dir.join("schema.out"), "").expect("Failed to write output");
+    let status = runner
+        .run("generate", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(status, Status::UpToDate);
+    assert!(status.success());
+    assert_eq!(runner.executor().commands().len(), 1);
+    assert_eq!(*up_to_date.lock().unwrap(), vecs!["generate"]);
+
+    let runner = Runner::with_executor(&config, RecordingExecutor::default()).force(true);
+    runner
+        .run("generate", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(runner.executor().commands(), vec![vecs!["codegen"]]);
+
+    std::fs::remove_dir_all(&dir).expect("Failed to remove directory");
+}
+
+#[test]
+fn test_echo() {
+    let config_str = r#"
+        echo = true
+
+        [[recipes]]
+        names = ["greet"]
+        arguments = ["*names"]
+        command = ["echo", "Hello,", ["*names"]]
+
+        [[recipes]]
+        names = ["quiet"]
+        echo = false
+        interpreter = "python3"
+        script = "print('quiet')"
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+
+    let job = runner
+        .prepare("greet", VecDeque::from(vecs!["Jane Doe", "O'Brien", ""]))
+        .expect("Failed to prepare recipe");
+    assert!(job.echo);
+    assert_eq!(
+        job.command_line(),
+        r"echo Hello, 'Jane Doe' 'O'\''Brien' ''"
+    );
+
+    let job = runner
+        .prepare("quiet", VecDeque::new())
+        .expect("Failed to prepare recipe");
+    assert!(!job.echo);
+    assert_eq!(job.command_line(), "python3 <script>");
+
+    let echoed = Mutex::new(Vec::new());
+    let on_notice = |notice: &Notice| {
+        if let Notice::Echo { job } = *notice {
+            echoed.lock().unwrap().push(job.command_line());
+        }
+    };
+    let runner = Runner::with_executor(&config, RecordingExecutor::default()).on_notice(&on_notice);
+    runner
+        .run("greet", VecDeque::from(vecs!["World"]))
+        .expect("Failed to run recipe");
+    runner
+        .run("quiet", VecDeque::new())
+        .expect("Failed to run recipe");
+    assert_eq!(*echoed.lock().unwrap(), vecs!["echo Hello, World"]);
+}
+
+#[test]
+fn test_structured_errors() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["greet"]
+        arguments = ["name"]
+        command = ["echo", ["?name"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let recipe = &config.recipes[0];
+
+    let err = recipe.resolve(VecDeque::new()).unwrap_err();
+    assert!(matches!(err, Error::MissingArgument { ref name, .. } if name == "name"));
+    let err = recipe.resolve(VecDeque::from(vecs!["Alice"])).unwrap_err();
+    assert!(matches!(err, Error::TypeMismatch { ref name, .. } if name == "name"));
+    assert_eq!(
+        err.to_string(),
+        "Argument \"name\" defined as Required but referenced as ?Optional"
+    );
+
+    let runner = Runner::new(&config);
+    let err = runner.resolve("greet", VecDeque::new()).unwrap_err();
+    assert_eq!(
+        format!("{err:#}"),
+        "Error resolving recipe \"greet\": While resolving argument \"name\": Required argument not provided"
+    );
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::MissingArgument { .. })
+    ));
+    let err = runner.resolve("nope", VecDeque::new()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::RecipeNotFound {
+            name: "nope".to_string()
+        })
+    );
+}
+
+#[test]
+fn test_prefix_matching() {
+    let config_str = r#"
+        match_prefix = true
+        [[recipes]]
+        names = ["build"]
+        command = ["cargo", "build"]
+        [[recipes]]
+        names = ["bench"]
+        command = ["cargo", "bench"]
+        [[recipes]]
+        names = ["test"]
+        command = ["cargo", "test"]
+    "#;
+    let mut config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+
+    assert_eq!(
+        config.lookup("bu").map(|r| &r.names[0]),
+        Ok(&vecs!["build"][0])
+    );
+    assert_eq!(
+        config.lookup("t").map(|r| &r.names[0]),
+        Ok(&vecs!["test"][0])
+    );
+    assert_eq!(
+        config.lookup("b").unwrap_err(),
+        Error::AmbiguousPrefix {
+            prefix: "b".to_string(),
+            candidates: vecs!["build", "bench"],
+        }
+    );
+
+    // Prefix matching is opt-in
+    config.match_prefix = false;
+    assert_eq!(
+        config.lookup("bu").unwrap_err(),
+        Error::RecipeNotFound {
+            name: "bu".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_run_all() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["fmt"]
+        command = ["cargo", "fmt"]
+        [[recipes]]
+        names = ["test"]
+        arguments = ["*rest"]
+        command = ["cargo", "test", ["*rest"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+
+    let invocations = vec![
+        Invocation::new("fmt".to_string(), VecDeque::new()),
+        Invocation::new("test".to_string(), VecDeque::from(vecs!["--lib"])),
+    ];
+    let runner = Runner::with_executor(&config, RecordingExecutor::default());
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![vecs!["cargo", "fmt"], vecs!["cargo", "test", "--lib"]]
+    );
+
+    // Stops on first failure
+    let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Exited(1)));
+    let status = runner.run_all(&invocations).expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(1));
+    assert_eq!(runner.executor().commands(), vec![vecs!["cargo", "fmt"]]);
+}
+
+#[test]
+fn test_run_hooks() {
+    /// Hooks recording what happened to each invocation, and adding an argument to every job.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl RunHooks for Recorder {
+        fn error(&self, index: usize, err: anyhow::Error) -> anyhow::Error {
+            err.context(format!("In invocation {index}"))
+        }
+
+        fn before(&self, _index: usize, mut job: Job) -> anyhow::Result<Job> {
+            job.command.push("--verbose".to_string());
+            Ok(job)
+        }
+
+        fn skipped(&self, index: usize) {
+            self.0.lock().unwrap().push(format!("{index} skipped"));
+        }
+
+        fn finished(&self, index: usize, _job: &Job, status: Status, _duration: Duration) {
+            self.0.lock().unwrap().push(format!("{index} {status}"));
+        }
+    }
+
+    let config_str = r#"
+        [[recipes]]
+        names = ["skipped"]
+        when = { os = "plan9" }
+        command = ["plumb"]
+        [[recipes]]
+        names = ["test"]
+        arguments = ["name"]
+        command = ["cargo", "test", ["name"]]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::with_executor(&config, RecordingExecutor::default());
+
+    let invocations = vec![
+        Invocation::new("skipped".to_string(), VecDeque::new()),
+        Invocation::new("test".to_string(), VecDeque::from(vecs!["runner"])),
+    ];
+    let hooks = Recorder::default();
+    runner
+        .check_all(&invocations, &hooks)
+        .expect("Failed to check recipes");
+    let status = runner
+        .execute_all(&invocations, &hooks)
+        .expect("Failed to run recipes");
+    assert_eq!(status, Status::Exited(0));
+    assert_eq!(
+        runner.executor().commands(),
+        vec![vecs!["cargo", "test", "runner", "--verbose"]]
+    );
+    assert_eq!(
+        *hooks.0.lock().unwrap(),
+        vecs!["0 skipped", "1 exit status: 0"]
+    );
+
+    // Errors are converted by the hooks, before anything runs
+    let invocations = vec![Invocation::new("test".to_string(), VecDeque::new())];
+    let err = runner.check_all(&invocations, &hooks).unwrap_err();
+    assert_eq!(err.to_string(), "In invocation 0");
+    assert_eq!(runner.executor().commands().len(), 1);
+}
+
+#[test]
+fn test_execute_parallel() {
+    let config = Config::default();
+    let commands = vec![
+        ("lint".to_string(), Job::new(vecs!["cargo", "clippy"])),
+        ("test".to_string(), Job::new(vecs!["cargo", "test"])),
+        ("check".to_string(), Job::new(vecs!["npm", "run", "check"])),
+    ];
+    let runner = Runner::with_executor(&config, RecordingExecutor::default());
+    let max_jobs = NonZeroUsize::new(2).unwrap();
+    let status = runner
+        .execute_parallel(&commands, max_jobs, false)
+        .expect("Failed to execute commands");
+    assert_eq!(status, Status::Exited(0));
+
+    let mut recorded = runner.executor().commands();
+    recorded.sort();
+    assert_eq!(
+        recorded,
+        vec![
+            vecs!["cargo", "clippy"],
+            vecs!["cargo", "test"],
+            vecs!["npm", "run", "check"]
+        ]
+    );
+
+    let finished = Mutex::new(Vec::new());
+    let runner = Runner::with_executor(&config, RecordingExecutor::new(Status::Exited(1)));
+    let max_jobs = NonZeroUsize::new(1).unwrap();
+    let status = runner
+        .execute_parallel_with(&commands, max_jobs, false, |i, status, _| {
+            finished.lock().unwrap().push((i, status));
+        })
+        .expect("Failed to execute commands");
+    assert_eq!(status, Status::Exited(1));
+    // No more jobs are started after the first one failed
+    assert_eq!(finished.into_inner().unwrap(), vec![(0, Status::Exited(1))]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_signaled_status() {
+    let command = vecs!["sh", "-c", "kill -TERM $$"];
+    let status = ProcessExecutor
+        .execute(&Job::new(command))
+        .expect("Failed to execute command");
+    assert_eq!(status, Status::Signaled(15));
+    assert_eq!(status.code(), 143);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_timeout() {
+    let config_str = r#"
+        [[recipes]]
+        names = ["slow"]
+        timeout = "100ms"
+        command = ["sleep", "5"]
+    "#;
+    let config: Config = toml::from_str(config_str).expect("Failed to parse config file");
+    let runner = Runner::new(&config);
+    let job = runner
+        .prepare("slow", VecDeque::new())
+        .expect("Failed to prepare recipe");
+    assert_eq!(job.timeout, Some(Duration::from_millis(100)));
+
+    let started = Instant::now();
+    let status = runner.execute(&job).expect("Failed to execute command");
+    assert_eq!(status, Status::TimedOut);
+    assert_eq!(status.code(), 124);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cancel() {
+    use jiu::executor::Cancel;
+
+    let cancel = Cancel::default();
+    let job = Job {
+        retries: 3,
+        cancel: Some(cancel.clone()),
+        ..Job::new(vecs!["sleep", "5"])
+    };
+    let config = Config::default();
+    let runner = Runner::new(&config);
+
+    let started = Instant::now();
+    let status = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            cancel.cancel();
+        });
+        runner.execute(&job).expect("Failed to execute command")
+    });
+    assert_eq!(status, Status::Cancelled);
+    assert_eq!(status.code(), 130);
+    // Cancelled jobs are not retried
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    cancel.reset();
+    let job = Job {
+        command: vecs!["true"],
+        ..job
+    };
+    assert_eq!(runner.execute(&job).unwrap(), Status::Exited(0));
+}
+
+#[cfg(all(unix, feature = "cli"))]
+#[test]
+fn test_exec_timeout() {
+    let config_str = r#"
+        exec = true
+
+        [[recipes]]
+        names = ["slow"]
+        timeout = "100ms"
+        command = ["sleep", "5"]
+    "#;
+    assert_eq!(run_binary("exec-timeout", config_str, &["slow"]), Some(124));
+}
+
+#[cfg(all(unix, feature = "cli"))]
+#[test]
+fn test_exec_success_codes() {
+    let config_str = r#"
+        exec = true
+
+        [[recipes]]
+        names = ["diff"]
+        success_codes = [0, 1]
+        command = ["sh", "-c", "exit 1"]
+    "#;
+    assert_eq!(
+        run_binary("exec-success-codes", config_str, &["diff"]),
+        Some(0)
+    );
+}
+
+#[cfg(all(unix, feature = "cli"))]
+#[test]
+fn test_signal_stops_sequence() {
+    let dir = std::env::temp_dir().join(format!("jiu-test-signal-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    // The first recipe handles the forwarded signal and succeeds, while the second one should not start
+    let config_str = r#"
+        [[recipes]]
+        names = ["serve"]
+        command = ["sh", "-c", "trap 'exit 0' TERM; sleep 5 & wait"]
+
+        [[recipes]]
+        names = ["next"]
+        command = ["touch", "next"]
+    "#;
+    std::fs::write(dir.join(".jiu.toml"), config_str).expect("Failed to write config file");
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_jiu"))
+        .args(["serve", "+", "next"])
+        .current_dir(&dir)
+        .env("JIU_HISTORY", dir.join("history"))
+        .spawn()
+        .expect("Failed to run jiu");
+    std::thread::sleep(Duration::from_millis(500));
+    let pid = libc::pid_t::try_from(child.id()).expect("Invalid process ID");
+    // SAFETY: `kill` has no memory safety requirements
+    unsafe {
+        libc::kill(pid, libc::SIGTERM);
+    }
+    let status = child.wait().expect("Failed to wait for jiu");
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+    assert!(!dir.join("next").exists());
+
+    std::fs::remove_dir_all(&dir).expect("Failed to remove directory");
+}